@@ -0,0 +1,101 @@
+use core::{
+    fmt,
+    net::{AddrParseError, Ipv4Addr},
+    str::FromStr,
+};
+
+use crate::Address;
+
+/// What to do with traffic matching a route: forward it to a next-hop, or
+/// drop it. Dropping is silent for `Blackhole`, while `Unreachable` routes
+/// are meant to be answered with an ICMP unreachable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteAction<A: Address = Ipv4Addr> {
+    Forward(A),
+    Blackhole,
+    Unreachable,
+}
+
+/// Writes the next-hop of a `Forward`, or `blackhole` / `unreachable`.
+impl<A: Address> fmt::Display for RouteAction<A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteAction::Forward(next_hop) => write!(f, "{next_hop}"),
+            RouteAction::Blackhole => f.write_str("blackhole"),
+            RouteAction::Unreachable => f.write_str("unreachable"),
+        }
+    }
+}
+
+/// Parses the output of `Display`, so tables of actions work with
+/// `load_from_str`.
+impl<A: Address> FromStr for RouteAction<A> {
+    type Err = AddrParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "blackhole" => Ok(RouteAction::Blackhole),
+            "unreachable" => Ok(RouteAction::Unreachable),
+            _ => s.parse().map(RouteAction::Forward),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::ActionTable;
+
+    #[test]
+    fn test_blackhole_overrides_forward() {
+        let mut table = ActionTable::new();
+        table
+            .insert_cidr(
+                "10.0.0.0/8",
+                RouteAction::Forward(Ipv4Addr::new(192, 168, 0, 1)),
+            )
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", RouteAction::Blackhole)
+            .unwrap();
+        table
+            .insert_cidr("10.0.2.0/24", RouteAction::Unreachable)
+            .unwrap();
+
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(RouteAction::Blackhole)
+        );
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(RouteAction::Unreachable)
+        );
+        assert_eq!(
+            table.lookup("10.9.0.1").unwrap(),
+            Some(RouteAction::Forward(Ipv4Addr::new(192, 168, 0, 1)))
+        );
+        assert_eq!(table.lookup("8.8.8.8").unwrap(), None);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let mut table = ActionTable::new();
+        table
+            .load_from_str(
+                "10.0.0.0/8 192.168.0.1\n10.0.1.0/24 blackhole\n10.0.2.0/24 unreachable\n",
+            )
+            .unwrap();
+        assert_eq!(
+            table.to_string(),
+            "10.0.0.0/8 -> 192.168.0.1\n10.0.1.0/24 -> blackhole\n10.0.2.0/24 -> unreachable\n"
+        );
+
+        let mut copy = ActionTable::new();
+        copy.load_from_str(&table.to_string()).unwrap();
+        assert_eq!(copy, table);
+
+        assert!("drop".parse::<RouteAction>().is_err());
+    }
+}
@@ -0,0 +1,189 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+    fmt,
+    net::{AddrParseError, IpAddr, Ipv4Addr, Ipv6Addr},
+    ops::{Add, BitAnd, BitOr, BitXor, Not, Shl, Shr, Sub},
+    str::FromStr,
+};
+
+use crate::RotabError;
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u32 {}
+    impl Sealed for u128 {}
+    impl Sealed for core::net::Ipv4Addr {}
+    impl Sealed for core::net::Ipv6Addr {}
+}
+
+/// The unsigned integer an address is stored as in the trie.
+pub trait Bits:
+    Copy
+    + Eq
+    + Ord
+    + fmt::Debug
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+    + sealed::Sealed
+{
+    const BITS: u32;
+    const ZERO: Self;
+    const ONE: Self;
+    const MAX: Self;
+
+    fn leading_zeros(self) -> u32;
+    fn trailing_zeros(self) -> u32;
+    fn checked_shl(self, rhs: u32) -> Option<Self>;
+}
+
+macro_rules! impl_bits {
+    ($($t:ty),*) => {
+        $(
+            impl Bits for $t {
+                const BITS: u32 = <$t>::BITS;
+                const ZERO: Self = 0;
+                const ONE: Self = 1;
+                const MAX: Self = <$t>::MAX;
+
+                fn leading_zeros(self) -> u32 {
+                    <$t>::leading_zeros(self)
+                }
+
+                fn trailing_zeros(self) -> u32 {
+                    <$t>::trailing_zeros(self)
+                }
+
+                fn checked_shl(self, rhs: u32) -> Option<Self> {
+                    <$t>::checked_shl(self, rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_bits!(u32, u128);
+
+/// An IP address family the trie can be keyed by.
+pub trait Address:
+    Copy
+    + Ord
+    + fmt::Debug
+    + fmt::Display
+    + FromStr<Err = AddrParseError>
+    + Into<IpAddr>
+    + sealed::Sealed
+{
+    type Bits: Bits;
+
+    const BITS: u32 = Self::Bits::BITS;
+
+    fn to_bits(self) -> Self::Bits;
+    fn from_bits(bits: Self::Bits) -> Self;
+}
+
+impl Address for Ipv4Addr {
+    type Bits = u32;
+
+    fn to_bits(self) -> u32 {
+        Ipv4Addr::to_bits(self)
+    }
+
+    fn from_bits(bits: u32) -> Self {
+        Ipv4Addr::from_bits(bits)
+    }
+}
+
+impl Address for Ipv6Addr {
+    type Bits = u128;
+
+    fn to_bits(self) -> u128 {
+        Ipv6Addr::to_bits(self)
+    }
+
+    fn from_bits(bits: u128) -> Self {
+        Ipv6Addr::from_bits(bits)
+    }
+}
+
+pub trait IntoAddr<A> {
+    fn into_addr(self) -> Result<A, AddrParseError>;
+}
+
+impl<A: Address> IntoAddr<A> for A {
+    fn into_addr(self) -> Result<A, AddrParseError> {
+        Ok(self)
+    }
+}
+
+impl<A: Address> IntoAddr<A> for &str {
+    fn into_addr(self) -> Result<A, AddrParseError> {
+        A::from_str(self)
+    }
+}
+
+impl<A: Address> IntoAddr<A> for String {
+    fn into_addr(self) -> Result<A, AddrParseError> {
+        A::from_str(&self)
+    }
+}
+
+impl<A: Address> IntoAddr<A> for &String {
+    fn into_addr(self) -> Result<A, AddrParseError> {
+        A::from_str(self)
+    }
+}
+
+pub(crate) fn mask<B: Bits>(prefix_len: u8) -> B {
+    B::MAX
+        .checked_shl(B::BITS - prefix_len as u32)
+        .unwrap_or(B::ZERO)
+}
+
+pub(crate) fn bit_at<B: Bits>(network: B, i: u8) -> usize {
+    ((network >> (B::BITS - i as u32 - 1)) & B::ONE == B::ONE) as usize
+}
+
+/// Packs a bit path, most significant bit first, into the network address it
+/// leads to, with the remaining host bits zero. Any nonzero element counts as
+/// a set bit, and bits past the 32nd are ignored.
+pub fn bits_to_ipv4(bits: &[u8]) -> Ipv4Addr {
+    bits_to_addr(bits)
+}
+
+/// Parses an IPv4 address more forgivingly than `Ipv4Addr::from_str`, for
+/// data sources that pad octets or fields: surrounding whitespace is
+/// trimmed and leading zeros are dropped from each octet, so
+/// `" 010.000.000.001 "` parses as `10.0.0.1`. Octets out of range, like in
+/// `256.0.0.0`, are still rejected.
+pub fn parse_lenient(s: &str) -> Result<Ipv4Addr, RotabError> {
+    let octets = s
+        .trim()
+        .split('.')
+        .map(|octet| match octet.trim_start_matches('0') {
+            "" if !octet.is_empty() => "0",
+            octet => octet,
+        })
+        .collect::<Vec<_>>();
+
+    Ok(octets.join(".").parse()?)
+}
+
+pub(crate) fn bits_to_addr<A: Address>(bits: &[u8]) -> A {
+    let network = bits
+        .iter()
+        .take(A::BITS as usize)
+        .enumerate()
+        .filter(|&(_, &bit)| bit != 0)
+        .fold(A::Bits::ZERO, |network, (i, _)| {
+            network | A::Bits::ONE << (A::BITS - i as u32 - 1)
+        });
+
+    A::from_bits(network)
+}
@@ -0,0 +1,531 @@
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    format,
+    string::String,
+    vec::Vec,
+};
+
+use crate::{Address, Bits, Node, RouteRef, Table, addr::mask};
+
+/// What a table made with `Table::new_auto_aggregate` keeps to undo its
+/// merges, so it answers lookups like a table holding every route that was
+/// inserted.
+#[derive(Clone)]
+pub(crate) struct AutoAggregate<V, B> {
+    eq: fn(&V, &V) -> bool,
+    clone: fn(&V) -> V,
+    /// Prefixes merged into their parent and no longer in the trie, mapped
+    /// to whether a route was inserted for them, rather than only merged
+    /// from their own halves. A hidden prefix has the destination and
+    /// metadata of the closest route covering it.
+    hidden: BTreeMap<(B, u8), bool>,
+    /// Routes in the trie that weren't inserted, only made by merging their
+    /// halves.
+    synthesized: BTreeSet<(B, u8)>,
+}
+
+impl<V, B> AutoAggregate<V, B> {
+    pub(crate) fn clear(&mut self) {
+        self.hidden.clear();
+        self.synthesized.clear();
+    }
+}
+
+impl<V, A: Address> Table<V, A> {
+    /// Creates an empty table that merges sibling routes as they're
+    /// inserted, like calling `aggregate` after every insert: adding
+    /// `10.0.0.128/25` next to `10.0.0.0/25` with the same destination and
+    /// metadata leaves only `10.0.0.0/24`. Routes inserted through `entry`
+    /// aren't merged.
+    ///
+    /// The merged routes are remembered, so lookups give the same
+    /// destinations as in a table without merging: inserting or removing a
+    /// route at a merged prefix, e.g. removing `10.0.0.0/25` again, first
+    /// splits the merge as needed. Only `len`, `routes` and the other
+    /// listings show the merged view.
+    pub fn new_auto_aggregate() -> Self
+    where
+        V: PartialEq + Clone,
+    {
+        Self {
+            auto_aggregate: Some(AutoAggregate {
+                eq: V::eq,
+                clone: V::clone,
+                hidden: BTreeMap::new(),
+                synthesized: BTreeSet::new(),
+            }),
+            ..Self::new()
+        }
+    }
+
+    /// Merges the halves of the prefix into it, then the prefix with its
+    /// sibling, and so on up the trie while the parent's sibling matches
+    /// too. Does nothing unless the table was made with
+    /// `new_auto_aggregate`.
+    pub(crate) fn merge_at(&mut self, network: A::Bits, prefix_len: u8) {
+        let Some(eq) = self.auto_aggregate.as_ref().map(|auto| auto.eq) else {
+            return;
+        };
+
+        if (prefix_len as u32) < A::BITS {
+            self.merge_siblings(network, prefix_len + 1, eq);
+        }
+        self.merge_siblings(network, prefix_len, eq);
+    }
+
+    fn merge_siblings(&mut self, mut network: A::Bits, mut prefix_len: u8, eq: fn(&V, &V) -> bool) {
+        while prefix_len > 0 {
+            network = network & mask(prefix_len - 1);
+            if !self.merge_pair(network, prefix_len, eq) {
+                break;
+            }
+            prefix_len -= 1;
+        }
+    }
+
+    /// Merges the routes for the lower half `network` and its sibling into
+    /// their parent, if both have the same destination and metadata and the
+    /// parent has no route or that same one. Returns whether they merged.
+    pub(crate) fn merge_pair(
+        &mut self,
+        network: A::Bits,
+        prefix_len: u8,
+        eq: fn(&V, &V) -> bool,
+    ) -> bool {
+        let sibling = network | A::Bits::ONE << (A::BITS - prefix_len as u32);
+        let same = |a: &Node<V, A::Bits>, b: &Node<V, A::Bits>| match (&a.dest, &b.dest) {
+            (Some(a_dest), Some(b_dest)) => eq(a_dest, b_dest) && a.meta == b.meta,
+            _ => false,
+        };
+
+        let (Some(lower), Some(upper)) = (
+            self.find_route(network, prefix_len),
+            self.find_route(sibling, prefix_len),
+        ) else {
+            return false;
+        };
+        if !same(lower, upper) {
+            return false;
+        }
+        // A parent hidden by an earlier merge stays hidden, with the halves
+        // as more specific routes under it.
+        if self.is_hidden(network, prefix_len - 1) {
+            return false;
+        }
+        let parent_installed = match self.find_route(network, prefix_len - 1) {
+            Some(parent) if !same(parent, lower) => return false,
+            parent => parent.is_some(),
+        };
+        let meta = lower.meta;
+
+        if let Some(auto) = &mut self.auto_aggregate {
+            for half in [network, sibling] {
+                let inserted = !auto.synthesized.remove(&(half, prefix_len));
+                auto.hidden.insert((half, prefix_len), inserted);
+            }
+            if !parent_installed {
+                auto.synthesized.insert((network, prefix_len - 1));
+            }
+        }
+
+        self.remove_prefix(sibling, prefix_len);
+        let dest = self.remove_prefix(network, prefix_len);
+        // A parent with the same route already covers both halves.
+        if !parent_installed && let Some(dest) = dest {
+            self.install_prefix(network, prefix_len - 1, dest, meta);
+        }
+
+        true
+    }
+
+    /// Puts the prefix back in the trie if a merge hid it, along with its
+    /// own halves, so that its route can be changed as in a table without
+    /// merging.
+    pub(crate) fn unmerge(&mut self, network: A::Bits, prefix_len: u8) {
+        if self.auto_aggregate.is_none() {
+            return;
+        }
+
+        // Split from the closest route covering the prefix down to it.
+        let mut top = prefix_len;
+        while self.is_hidden(network & mask(top), top) {
+            top -= 1;
+        }
+        for len in top..=prefix_len {
+            let network = network & mask(len);
+            if (len as u32) < A::BITS && self.is_hidden(network, len + 1) {
+                self.split_merged(network, len);
+            }
+        }
+    }
+
+    /// Puts every hidden route back in the trie and removes the synthesized
+    /// ones, leaving the routes of a table without merging until
+    /// `merge_all`.
+    pub(crate) fn unmerge_all(&mut self) {
+        let Some(auto) = &self.auto_aggregate else {
+            return;
+        };
+
+        // Parents before their halves, which may be merged routes too.
+        let mut merged = auto
+            .hidden
+            .keys()
+            .map(|&(network, len)| (len - 1, network & mask(len - 1)))
+            .collect::<Vec<_>>();
+        merged.sort_unstable();
+        merged.dedup();
+
+        for (len, network) in merged {
+            self.split_merged(network, len);
+        }
+    }
+
+    /// Merges every pair of siblings that `new_auto_aggregate` would have,
+    /// after a change made with the merges undone by `unmerge_all`.
+    pub(crate) fn merge_all(&mut self) {
+        if let Some(eq) = self.auto_aggregate.as_ref().map(|auto| auto.eq) {
+            self.aggregate_with(eq);
+        }
+    }
+
+    /// The node holding the route inserted for exactly the prefix: its own,
+    /// or for a prefix hidden by a merge, that of the route it was merged
+    /// into. Routes only made by merging weren't inserted, so they're left
+    /// out.
+    pub(crate) fn inserted_route(
+        &self,
+        network: A::Bits,
+        prefix_len: u8,
+    ) -> Option<&Node<V, A::Bits>> {
+        let Some(auto) = &self.auto_aggregate else {
+            return self.find_route(network, prefix_len);
+        };
+
+        match auto.hidden.get(&(network, prefix_len)) {
+            Some(false) => None,
+            Some(true) => {
+                let mut top = prefix_len - 1;
+                while self.is_hidden(network & mask(top), top) {
+                    top -= 1;
+                }
+                self.find_route(network & mask(top), top)
+            }
+            None if auto.synthesized.contains(&(network, prefix_len)) => None,
+            None => self.find_route(network, prefix_len),
+        }
+    }
+
+    /// The routes inserted for prefixes covering `ip`, up to `max_len`, from
+    /// least to most specific. Hidden ones come with the node of the route
+    /// they were merged into.
+    pub(crate) fn inserted_matches(
+        &self,
+        ip: A::Bits,
+        max_len: u8,
+    ) -> Vec<RouteRef<'_, V, A::Bits>> {
+        let Some(auto) = &self.auto_aggregate else {
+            return Vec::new();
+        };
+
+        let mut routes = Vec::new();
+        for node in self.matching_nodes(ip, max_len) {
+            if !auto.synthesized.contains(&(node.network, node.prefix_len)) {
+                routes.push((node.network, node.prefix_len, node));
+            }
+            for len in node.prefix_len + 1..=max_len {
+                let network = ip & mask(len);
+                match auto.hidden.get(&(network, len)) {
+                    Some(true) => routes.push((network, len, node)),
+                    Some(false) => {}
+                    None => break,
+                }
+            }
+        }
+
+        routes
+    }
+
+    /// Every route inserted in the table, hidden ones included, in the order
+    /// of `routes`.
+    pub(crate) fn inserted_routes(&self) -> Vec<RouteRef<'_, V, A::Bits>> {
+        let route = |(network, prefix_len): (A::Bits, u8)| {
+            Some((
+                network,
+                prefix_len,
+                self.inserted_route(network, prefix_len)?,
+            ))
+        };
+        let mut routes = self
+            .routes()
+            .filter_map(|(network, prefix_len, _)| route((network.to_bits(), prefix_len)))
+            .collect::<Vec<_>>();
+
+        if let Some(auto) = &self.auto_aggregate {
+            routes.extend(
+                auto.hidden
+                    .iter()
+                    .filter(|&(_, &inserted)| inserted)
+                    .filter_map(|(&prefix, _)| route(prefix)),
+            );
+            routes.sort_unstable_by_key(|&(network, prefix_len, _)| (network, prefix_len));
+        }
+
+        routes
+    }
+
+    fn is_hidden(&self, network: A::Bits, prefix_len: u8) -> bool {
+        self.auto_aggregate
+            .as_ref()
+            .is_some_and(|auto| auto.hidden.contains_key(&(network, prefix_len)))
+    }
+
+    /// Puts the halves hidden under the route for the prefix back in the
+    /// trie, and removes the route if it was only made by merging them.
+    fn split_merged(&mut self, network: A::Bits, prefix_len: u8) {
+        let upper = network | A::Bits::ONE << (A::BITS - prefix_len as u32 - 1);
+        let Some(auto) = &mut self.auto_aggregate else {
+            return;
+        };
+
+        let clone = auto.clone;
+        let synthesized = auto.synthesized.remove(&(network, prefix_len));
+        for half in [network, upper] {
+            if auto.hidden.remove(&(half, prefix_len + 1)) == Some(false) {
+                auto.synthesized.insert((half, prefix_len + 1));
+            }
+        }
+
+        let Some((dest, meta)) = self
+            .find_route(network, prefix_len)
+            .and_then(|route| Some((clone(route.dest.as_ref()?), route.meta)))
+        else {
+            return;
+        };
+        self.install_prefix(network, prefix_len + 1, clone(&dest), meta);
+        self.install_prefix(upper, prefix_len + 1, dest, meta);
+        if synthesized {
+            self.remove_prefix(network, prefix_len);
+        }
+    }
+
+    /// Checks that every hidden prefix is hidden with its sibling under a
+    /// route or another hidden prefix, and that every synthesized route is
+    /// in the trie with its halves hidden.
+    pub(crate) fn validate_merges(&self) -> Result<(), String> {
+        let Some(auto) = &self.auto_aggregate else {
+            return Ok(());
+        };
+        let prefix = |network: A::Bits, len: u8| format!("{}/{len}", A::from_bits(network));
+
+        for &(network, len) in auto.hidden.keys() {
+            let sibling = network ^ A::Bits::ONE << (A::BITS - len as u32);
+            if !auto.hidden.contains_key(&(sibling, len)) {
+                return Err(format!(
+                    "{} is hidden without its sibling",
+                    prefix(network, len)
+                ));
+            }
+            if self.find_route(network, len).is_some() {
+                return Err(format!(
+                    "{} is hidden but has a route",
+                    prefix(network, len)
+                ));
+            }
+            let parent = network & mask(len - 1);
+            if !auto.hidden.contains_key(&(parent, len - 1))
+                && self.find_route(parent, len - 1).is_none()
+            {
+                return Err(format!(
+                    "{} is hidden without a route covering it",
+                    prefix(network, len)
+                ));
+            }
+        }
+        for &(network, len) in &auto.synthesized {
+            if self.find_route(network, len).is_none() || !self.is_hidden(network, len + 1) {
+                return Err(format!(
+                    "{} is synthesized but not a merged route",
+                    prefix(network, len)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use crate::{InsertOutcome, Match, MergePolicy, RotabError, Table};
+
+    #[test]
+    fn test_auto_aggregate_keeps_merged_routes() {
+        let mut table = Table::<u32>::new_auto_aggregate();
+        table.insert_cidr("10.0.0.0/25", 1).unwrap();
+        table.insert_cidr("10.0.0.128/25", 1).unwrap();
+        assert_eq!(table.len(), 1);
+
+        // The /24 wasn't inserted, so it can't be removed, and a route for
+        // it doesn't override the /25s.
+        assert!(!table.remove_cidr("10.0.0.0/24").unwrap());
+        assert_eq!(table.insert_cidr("10.0.0.0/24", 2).unwrap(), None);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(1));
+        assert_eq!(table.len(), 3);
+
+        assert!(table.remove_cidr("10.0.0.0/25").unwrap());
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(2));
+        assert_eq!(table.lookup("10.0.0.200").unwrap(), Some(1));
+        assert_eq!(table.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_auto_aggregate_keeps_parent_route() {
+        let mut table = Table::<u32>::new_auto_aggregate();
+        table.insert_cidr("10.0.0.0/24", 1).unwrap();
+        table.insert_cidr("10.0.0.0/25", 2).unwrap();
+        table.insert_cidr("10.0.0.128/25", 2).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get_exact("10.0.0.0", 24).unwrap(), Some(1));
+
+        // With the same route, the /24 just hides the /25s, which come back
+        // when it's removed.
+        table.insert_cidr("10.0.0.0/24", 2).unwrap();
+        assert_eq!(table.len(), 1);
+        assert!(table.remove_cidr("10.0.0.0/24").unwrap());
+        assert!(table.remove_cidr("10.0.0.128/25").unwrap());
+        assert_eq!(
+            table
+                .routes()
+                .map(|(n, l, &d)| (n, l, d))
+                .collect::<Vec<_>>(),
+            vec![(Ipv4Addr::new(10, 0, 0, 0), 25, 2)]
+        );
+        assert_eq!(table.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_auto_aggregate_exact_queries_see_merged_routes() {
+        let mut table = Table::<u32>::new_auto_aggregate();
+        table.insert_cidr("10.0.0.0/25", 1).unwrap();
+        table.insert_cidr("10.0.0.128/25", 1).unwrap();
+        assert_eq!(table.len(), 1);
+
+        assert_eq!(table.get_exact("10.0.0.0", 25).unwrap(), Some(1));
+        assert_eq!(table.get_exact("10.0.0.0", 24).unwrap(), None);
+        assert!(table.contains_exact("10.0.0.128", "10.0.0.255").unwrap());
+        assert!(!table.contains_exact("10.0.0.0", "10.0.0.255").unwrap());
+        assert_eq!(
+            table
+                .insert_with_outcome("10.0.0.0", "10.0.0.127", 1)
+                .unwrap(),
+            InsertOutcome::Unchanged
+        );
+
+        let detailed = Match {
+            dest: 1,
+            prefix_len: 25,
+            network: Ipv4Addr::new(10, 0, 0, 128),
+        };
+        assert_eq!(
+            table.lookup_detailed("10.0.0.200").unwrap(),
+            Some(detailed.clone())
+        );
+        assert_eq!(table.lookup_all("10.0.0.200").unwrap(), vec![detailed]);
+        assert_eq!(table.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_auto_aggregate_merge_sees_merged_routes() {
+        let mut table = Table::<u32>::new_auto_aggregate();
+        table.insert_cidr("10.0.0.0/25", 1).unwrap();
+        table.insert_cidr("10.0.0.128/25", 1).unwrap();
+
+        let mut other = Table::<u32>::new_auto_aggregate();
+        other.insert_cidr("10.0.0.0/25", 2).unwrap();
+        other.insert_cidr("10.0.1.0/25", 3).unwrap();
+        other.insert_cidr("10.0.1.128/25", 3).unwrap();
+
+        assert_eq!(
+            table.merge(&other, MergePolicy::Error),
+            Err(RotabError::MergeConflict(vec![(
+                IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)),
+                25
+            )]))
+        );
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(1));
+
+        table.merge(&other, MergePolicy::KeepExisting).unwrap();
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(1));
+        assert_eq!(table.get_exact("10.0.1.128", 25).unwrap(), Some(3));
+        assert_eq!(table.get_exact("10.0.1.0", 24).unwrap(), None);
+        assert_eq!(table.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_auto_aggregate_matches_plain_table() {
+        // A small LCG, so failures reproduce from the seed.
+        let mut state = 0x2545_F491_4F6C_DD1D_u64;
+        let mut next = |n: u32| {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1_442_695_040_888_963_407);
+            (state >> 33) as u32 % n
+        };
+
+        for _ in 0..50 {
+            let mut merged = Table::<u32>::new_auto_aggregate();
+            let mut plain = Table::<u32>::new();
+
+            for _ in 0..200 {
+                // Prefixes of 10.0.0.0/22, of which a few hundred exist, so
+                // siblings and nested routes are common.
+                let prefix_len = 22 + next(5) as u8;
+                let network = (0x0A00_0000 | next(1 << 10)) & !(u32::MAX >> prefix_len);
+                let cidr = format!("{}/{prefix_len}", Ipv4Addr::from_bits(network));
+
+                if next(3) == 0 {
+                    assert_eq!(
+                        merged.remove_cidr(&cidr),
+                        plain.remove_cidr(&cidr),
+                        "{cidr}"
+                    );
+                } else {
+                    let dest = next(2);
+                    assert_eq!(
+                        merged.insert_cidr(&cidr, dest),
+                        plain.insert_cidr(&cidr, dest),
+                        "{cidr}"
+                    );
+                }
+                assert_eq!(merged.validate(), Ok(()));
+            }
+
+            assert!(merged.len() <= plain.len());
+            for ip in (0x0A00_0000..0x0A00_0400).map(Ipv4Addr::from_bits) {
+                assert_eq!(
+                    merged.lookup(ip).unwrap(),
+                    plain.lookup(ip).unwrap(),
+                    "{ip}"
+                );
+                assert_eq!(
+                    merged.lookup_all(ip).unwrap(),
+                    plain.lookup_all(ip).unwrap(),
+                    "{ip}"
+                );
+            }
+            for prefix_len in 22..=26 {
+                for network in (0x0A00_0000..0x0A00_0400).step_by(1 << (32 - prefix_len)) {
+                    let network = Ipv4Addr::from_bits(network);
+                    assert_eq!(
+                        merged.get_exact(network, prefix_len).unwrap(),
+                        plain.get_exact(network, prefix_len).unwrap(),
+                        "{network}/{prefix_len}"
+                    );
+                }
+            }
+        }
+    }
+}
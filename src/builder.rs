@@ -0,0 +1,143 @@
+use alloc::{vec, vec::Vec};
+use core::net::Ipv4Addr;
+
+use crate::{Address, IntoAddr, RotabError, Table};
+
+/// Collects routes and builds a `Table` from them in one pass.
+///
+/// Routes are installed shortest prefix first, so the result doesn't depend
+/// on the order they were added in. Adding the same prefix twice is an error.
+pub struct TableBuilder<V, A: Address = Ipv4Addr> {
+    routes: Vec<(A::Bits, A::Bits, V)>,
+    error: Option<RotabError>,
+}
+
+impl<V, A: Address> Default for TableBuilder<V, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, A: Address> TableBuilder<V, A> {
+    pub fn new() -> Self {
+        Self {
+            routes: vec![],
+            error: None,
+        }
+    }
+
+    /// Adds a route for `start..=end`. An invalid range is reported by
+    /// `build`.
+    pub fn add(&mut self, start: impl IntoAddr<A>, end: impl IntoAddr<A>, dest: V) -> &mut Self {
+        if self.error.is_none() {
+            match Table::<V, A>::parse_range(start, end) {
+                Ok((start, end)) => self.routes.push((start, end, dest)),
+                Err(e) => self.error = Some(e),
+            }
+        }
+
+        self
+    }
+
+    pub fn build(self) -> Result<Table<V, A>, RotabError>
+    where
+        V: Clone,
+    {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let mut prefixes = vec![];
+        for (start, end, dest) in self.routes {
+            for (first, last) in Table::<V, A>::range_to_blocks(start, end) {
+                let (network, prefix_len) = Table::<V, A>::prefix(first, last);
+                prefixes.push((prefix_len, network, dest.clone()));
+            }
+        }
+        prefixes.sort_by_key(|&(prefix_len, network, _)| (prefix_len, network));
+
+        if let Some(dup) = prefixes
+            .windows(2)
+            .find(|w| (w[0].0, w[0].1) == (w[1].0, w[1].1))
+        {
+            return Err(RotabError::DuplicatePrefix {
+                network: A::from_bits(dup[0].1).into(),
+                prefix_len: dup[0].0,
+            });
+        }
+
+        let mut table = Table::new();
+        for (prefix_len, network, dest) in prefixes {
+            table.insert_prefix(network, prefix_len, dest);
+        }
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::RouteTable;
+
+    const ROUTES: [(&str, &str, Ipv4Addr); 6] = [
+        ("0.0.0.0", "255.255.255.255", Ipv4Addr::new(192, 168, 0, 0)),
+        ("10.0.0.0", "10.255.255.255", Ipv4Addr::new(192, 168, 0, 1)),
+        ("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 2)),
+        ("10.0.0.128", "10.0.0.191", Ipv4Addr::new(192, 168, 0, 3)),
+        ("10.0.0.130", "10.0.0.130", Ipv4Addr::new(192, 168, 0, 4)),
+        ("172.16.0.1", "172.16.0.6", Ipv4Addr::new(192, 168, 0, 5)),
+    ];
+
+    #[test]
+    fn test_build_matches_incremental() {
+        let mut incremental = RouteTable::new();
+        for (start, end, dest) in ROUTES {
+            incremental.insert_range(start, end, dest).unwrap();
+        }
+
+        let mut builder = TableBuilder::new();
+        for i in [4, 2, 5, 0, 3, 1] {
+            let (start, end, dest) = ROUTES[i];
+            builder.add(start, end, dest);
+        }
+        let built = builder.build().unwrap();
+
+        assert_eq!(built.len(), incremental.len());
+        assert!(built.routes().eq(incremental.routes()));
+        for ip in [
+            "8.8.8.8",
+            "10.1.0.1",
+            "10.0.0.1",
+            "10.0.0.129",
+            "10.0.0.130",
+            "172.16.0.0",
+            "172.16.0.3",
+        ] {
+            assert_eq!(built.lookup(ip).unwrap(), incremental.lookup(ip).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_build_errors() {
+        let mut builder = TableBuilder::<u32>::new();
+        builder
+            .add("10.0.0.0", "10.0.0.255", 1)
+            .add("10.0.0.x", "10.0.0.255", 2);
+        assert!(matches!(builder.build(), Err(RotabError::InvalidAddr(_))));
+
+        let mut builder = TableBuilder::<u32>::new();
+        builder
+            .add("10.0.0.0", "10.0.0.255", 1)
+            .add("10.0.0.0", "10.0.1.0", 2);
+        assert_eq!(
+            builder.build().err(),
+            Some(RotabError::DuplicatePrefix {
+                network: Ipv4Addr::new(10, 0, 0, 0).into(),
+                prefix_len: 24,
+            })
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::net::Ipv4Addr;
+
+use crate::{Address, IntoAddr, RotabError, Table};
+
+/// A `Table` with an LRU cache of lookup results in front of it, for
+/// traffic that keeps looking up the same few addresses. Misses are cached
+/// too. Updates go through the wrapper, which drops the cached results for
+/// the addresses they affect.
+pub struct CachedTable<V, A: Address = Ipv4Addr> {
+    table: Table<V, A>,
+    capacity: usize,
+    /// Cached results with the tick they were last used at.
+    entries: BTreeMap<A::Bits, (Option<V>, u64)>,
+    /// The address last used at each tick, oldest first.
+    recency: BTreeMap<u64, A::Bits>,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl<V, A: Address> CachedTable<V, A> {
+    /// Wraps `table` with a cache holding up to `capacity` addresses. A
+    /// capacity of 0 disables caching.
+    pub fn new(table: Table<V, A>, capacity: usize) -> Self {
+        Self {
+            table,
+            capacity,
+            entries: BTreeMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    pub fn table(&self) -> &Table<V, A> {
+        &self.table
+    }
+
+    pub fn into_inner(self) -> Table<V, A> {
+        self.table
+    }
+
+    /// Returns how many lookups were answered from the cache.
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns how many lookups had to walk the trie.
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+
+    pub fn lookup(&mut self, ip: impl IntoAddr<A>) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?;
+        let key = ip.to_bits();
+        self.tick += 1;
+
+        if let Some((dest, last_used)) = self.entries.get_mut(&key) {
+            self.recency.remove(last_used);
+            self.recency.insert(self.tick, key);
+            *last_used = self.tick;
+            self.hits += 1;
+
+            return Ok(dest.clone());
+        }
+
+        self.misses += 1;
+        let dest = self.table.lookup(ip)?;
+        if self.capacity > 0 {
+            if self.entries.len() == self.capacity
+                && let Some((_, oldest)) = self.recency.pop_first()
+            {
+                self.entries.remove(&oldest);
+            }
+            self.entries.insert(key, (dest.clone(), self.tick));
+            self.recency.insert(self.tick, key);
+        }
+
+        Ok(dest)
+    }
+
+    pub fn insert_range(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        let (start, end) = (start.into_addr()?, end.into_addr()?);
+        let previous = self.table.insert_range(start, end, dest)?;
+        self.invalidate(start, end);
+
+        Ok(previous)
+    }
+
+    pub fn remove_range(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<bool, RotabError> {
+        let (start, end) = (start.into_addr()?, end.into_addr()?);
+        let removed = self.table.remove_range(start, end)?;
+        self.invalidate(start, end);
+
+        Ok(removed)
+    }
+
+    /// Drops every cached result.
+    pub fn clear_cache(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    /// Drops the cached results for `start..=end`, the only addresses whose
+    /// lookups a change to that range can affect.
+    fn invalidate(&mut self, start: A, end: A) {
+        let stale = self
+            .entries
+            .range(start.to_bits()..=end.to_bits())
+            .map(|(&key, &(_, last_used))| (key, last_used))
+            .collect::<Vec<_>>();
+
+        for (key, last_used) in stale {
+            self.entries.remove(&key);
+            self.recency.remove(&last_used);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::RouteTable;
+
+    #[test]
+    fn test_cache_hits() {
+        let mut table = RouteTable::new();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        let mut table = CachedTable::new(table, 2);
+
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!((table.hits(), table.misses()), (0, 1));
+        assert_eq!(
+            table.lookup(Ipv4Addr::new(10, 0, 0, 1)).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!((table.hits(), table.misses()), (1, 1));
+
+        // 10.0.0.1 was used more recently than 10.0.0.2, so 10.0.0.2 is
+        // evicted to make room for 10.0.0.3.
+        table.lookup("10.0.0.2").unwrap();
+        table.lookup("10.0.0.1").unwrap();
+        table.lookup("10.0.0.3").unwrap();
+        assert_eq!((table.hits(), table.misses()), (2, 3));
+        table.lookup("10.0.0.1").unwrap();
+        assert_eq!((table.hits(), table.misses()), (3, 3));
+        table.lookup("10.0.0.2").unwrap();
+        assert_eq!((table.hits(), table.misses()), (3, 4));
+
+        assert!(table.lookup("10.0.0").is_err());
+    }
+
+    #[test]
+    fn test_cache_invalidation() {
+        let mut table = CachedTable::new(RouteTable::new(), 16);
+        assert_eq!(table.lookup("172.16.0.1").unwrap(), None);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), None);
+
+        table
+            .insert_range("172.16.0.0", "172.16.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_eq!(
+            table.lookup("172.16.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), None);
+        assert_eq!((table.hits(), table.misses()), (1, 3));
+
+        assert!(table.remove_range("172.16.0.0", "172.16.0.255").unwrap());
+        assert_eq!(table.lookup("172.16.0.1").unwrap(), None);
+        assert_eq!(table.misses(), 4);
+
+        let mut table = CachedTable::new(RouteTable::new(), 0);
+        table.lookup("10.0.0.1").unwrap();
+        table.lookup("10.0.0.1").unwrap();
+        assert_eq!((table.hits(), table.misses()), (0, 2));
+        assert!(table.into_inner().is_empty());
+    }
+}
@@ -0,0 +1,193 @@
+use core::net::Ipv4Addr;
+
+use crate::{Address, Table};
+
+/// A view into a single prefix of a `Table`, from `Table::entry`.
+pub enum Entry<'a, V, A: Address = Ipv4Addr> {
+    Occupied(OccupiedEntry<'a, V, A>),
+    Vacant(VacantEntry<'a, V, A>),
+}
+
+pub struct OccupiedEntry<'a, V, A: Address = Ipv4Addr> {
+    table: &'a mut Table<V, A>,
+    idx: u32,
+}
+
+pub struct VacantEntry<'a, V, A: Address = Ipv4Addr> {
+    table: &'a mut Table<V, A>,
+    network: A::Bits,
+    prefix_len: u8,
+}
+
+impl<'a, V, A: Address> Entry<'a, V, A> {
+    pub(crate) fn new(table: &'a mut Table<V, A>, network: A::Bits, prefix_len: u8) -> Self {
+        match table.find_node(network, prefix_len) {
+            Some(idx) if table.node(idx).is_terminal => {
+                Entry::Occupied(OccupiedEntry { table, idx })
+            }
+            _ => Entry::Vacant(VacantEntry {
+                table,
+                network,
+                prefix_len,
+            }),
+        }
+    }
+
+    /// Returns the entry's prefix as `(network, prefix_len)`.
+    pub fn prefix(&self) -> (A, u8) {
+        match self {
+            Entry::Occupied(entry) => entry.prefix(),
+            Entry::Vacant(entry) => entry.prefix(),
+        }
+    }
+
+    /// Inserts `dest` if the prefix has no route, and returns the route's
+    /// destination.
+    pub fn or_insert(self, dest: V) -> &'a mut V {
+        self.or_insert_with(|| dest)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, f: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(f()),
+        }
+    }
+
+    /// Calls `f` on the destination if the prefix has a route.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+
+        self
+    }
+}
+
+impl<'a, V, A: Address> OccupiedEntry<'a, V, A> {
+    pub fn prefix(&self) -> (A, u8) {
+        let node = self.table.node(self.idx);
+        (A::from_bits(node.network), node.prefix_len)
+    }
+
+    pub fn get(&self) -> &V {
+        self.table.node(self.idx).dest.as_ref().unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.table.generation += 1;
+        self.table.node_mut(self.idx).dest.as_mut().unwrap()
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.table.generation += 1;
+        self.table.node_mut(self.idx).dest.as_mut().unwrap()
+    }
+
+    /// Replaces the destination, returning the old one.
+    pub fn insert(&mut self, dest: V) -> V {
+        core::mem::replace(self.get_mut(), dest)
+    }
+
+    /// Removes the route, returning its destination.
+    pub fn remove(self) -> V {
+        let node = self.table.node(self.idx);
+        let (network, prefix_len) = (node.network, node.prefix_len);
+
+        self.table.remove_route(network, prefix_len).unwrap()
+    }
+}
+
+impl<'a, V, A: Address> VacantEntry<'a, V, A> {
+    pub fn prefix(&self) -> (A, u8) {
+        (A::from_bits(self.network), self.prefix_len)
+    }
+
+    /// Installs a route for the prefix, returning its destination.
+    pub fn insert(self, dest: V) -> &'a mut V {
+        let idx = self.table.insert_node(self.network, self.prefix_len);
+        self.table.count_route(self.prefix_len);
+        self.table.generation += 1;
+
+        let node = self.table.node_mut(idx);
+        node.is_terminal = true;
+        node.dest.insert(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::net::Ipv4Addr;
+
+    use super::*;
+    use crate::{RotabError, RouteTable};
+
+    #[test]
+    fn test_or_insert() {
+        let mut table = RouteTable::new();
+        let dest = table
+            .entry("10.0.0.0", "10.0.0.255")
+            .unwrap()
+            .or_insert(Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(*dest, Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(table.len(), 1);
+
+        let dest = table
+            .entry("10.0.0.0", "10.0.0.255")
+            .unwrap()
+            .or_insert(Ipv4Addr::new(192, 168, 0, 2));
+        assert_eq!(*dest, Ipv4Addr::new(192, 168, 0, 1));
+        assert_eq!(table.len(), 1);
+
+        // A covering route doesn't occupy the more specific entry.
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 8))
+            .unwrap();
+        let entry = table.entry("10.0.1.0", "10.0.1.255").unwrap();
+        assert!(matches!(entry, Entry::Vacant(_)));
+        assert_eq!(entry.prefix(), (Ipv4Addr::new(10, 0, 1, 0), 24));
+        entry.or_insert(Ipv4Addr::new(192, 168, 0, 3));
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 8))
+        );
+    }
+
+    #[test]
+    fn test_and_modify() {
+        let mut table = crate::Table::<u32>::new();
+        table.insert_cidr("10.0.0.0/24", 1).unwrap();
+
+        table
+            .entry("10.0.0.0", "10.0.0.255")
+            .unwrap()
+            .and_modify(|dest| *dest += 10)
+            .or_insert(0);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(11));
+
+        table
+            .entry("10.0.1.0", "10.0.1.255")
+            .unwrap()
+            .and_modify(|dest| *dest += 10)
+            .or_insert(0);
+        assert_eq!(table.lookup("10.0.1.1").unwrap(), Some(0));
+
+        let Entry::Occupied(entry) = table.entry("10.0.0.0", "10.0.0.255").unwrap() else {
+            panic!("expected an occupied entry");
+        };
+        assert_eq!(entry.remove(), 11);
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), None);
+
+        assert!(matches!(
+            table.entry("10.0.0.1", "10.0.0.6"),
+            Err(RotabError::NotSingleCidr { .. })
+        ));
+    }
+}
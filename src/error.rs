@@ -0,0 +1,177 @@
+use alloc::{string::String, vec::Vec};
+use core::{
+    error::Error,
+    fmt,
+    net::{AddrParseError, IpAddr},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RotabError {
+    /// An address failed to parse.
+    InvalidAddr(AddrParseError),
+    /// A prefix length is longer than the address, or not longer than the
+    /// prefix `split_prefix` was asked to split.
+    InvalidPrefixLen(u8),
+    /// A CIDR string, given whole, isn't `address/length`.
+    InvalidCidr(String),
+    /// A range's `start` comes after its `end`.
+    InvertedRange { start: IpAddr, end: IpAddr },
+    /// The range `start..=end` had to be exactly one aligned CIDR block,
+    /// but isn't.
+    NotSingleCidr { start: IpAddr, end: IpAddr },
+    /// `TableBuilder::build` was given the prefix more than once.
+    DuplicatePrefix { network: IpAddr, prefix_len: u8 },
+    /// `merge` under `MergePolicy::Error` found these prefixes in both
+    /// tables.
+    MergeConflict(Vec<(IpAddr, u8)>),
+    /// A line of `load_from_str` input, given whole, isn't a `CIDR dest`
+    /// pair with a destination that parses.
+    InvalidRoute(String),
+    /// `lookup_required` found no route for `ip`.
+    NoRoute { ip: IpAddr },
+    /// A netmask given to `insert_masked` has a zero bit before a one bit.
+    InvalidNetmask(IpAddr),
+    /// `try_insert_disjoint` found these installed routes covering or
+    /// inside the range.
+    Overlaps { conflicting: Vec<(IpAddr, u8)> },
+    /// `from_bytes` input is truncated or doesn't match its route count,
+    /// as described.
+    InvalidEncoding(String),
+    /// The prefix is installed with a lower admin distance than the route
+    /// being inserted, which is dropped.
+    LowerDistanceInstalled {
+        network: IpAddr,
+        prefix_len: u8,
+        admin_distance: u8,
+    },
+}
+
+impl fmt::Display for RotabError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RotabError::InvalidAddr(e) => write!(f, "invalid address: {e}"),
+            RotabError::InvalidPrefixLen(len) => write!(f, "invalid prefix length: /{len}"),
+            RotabError::InvalidCidr(cidr) => write!(f, "invalid CIDR: {cidr}"),
+            RotabError::InvertedRange { start, end } => {
+                write!(f, "range start {start} is greater than its end {end}")
+            }
+            RotabError::NotSingleCidr { start, end } => {
+                write!(f, "range {start}-{end} is not a single CIDR block")
+            }
+            RotabError::DuplicatePrefix {
+                network,
+                prefix_len,
+            } => write!(f, "prefix {network}/{prefix_len} was added more than once"),
+            RotabError::MergeConflict(conflicts) => {
+                write!(
+                    f,
+                    "{} prefixes are installed in both tables",
+                    conflicts.len()
+                )
+            }
+            RotabError::InvalidRoute(route) => write!(f, "invalid route: {route}"),
+            RotabError::NoRoute { ip } => write!(f, "no route to {ip}"),
+            RotabError::InvalidNetmask(netmask) => write!(f, "invalid netmask: {netmask}"),
+            RotabError::Overlaps { conflicting } => {
+                write!(f, "range overlaps {} installed routes", conflicting.len())
+            }
+            RotabError::InvalidEncoding(reason) => write!(f, "invalid table encoding: {reason}"),
+            RotabError::LowerDistanceInstalled {
+                network,
+                prefix_len,
+                admin_distance,
+            } => write!(
+                f,
+                "prefix {network}/{prefix_len} is installed with a lower admin distance of {admin_distance}"
+            ),
+        }
+    }
+}
+
+impl Error for RotabError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            RotabError::InvalidAddr(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<AddrParseError> for RotabError {
+    fn from(e: AddrParseError) -> Self {
+        RotabError::InvalidAddr(e)
+    }
+}
+
+/// An error from `Table::load_from_str`, with the 1-based line it occurred on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    pub line: usize,
+    pub error: RotabError,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.error)
+    }
+}
+
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{error::Error, net::Ipv4Addr, str::FromStr};
+
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let e = RotabError::from(Ipv4Addr::from_str("10.0.0").unwrap_err());
+        assert_eq!(
+            e.to_string(),
+            "invalid address: invalid IPv4 address syntax"
+        );
+        assert!(e.source().is_some());
+
+        assert_eq!(
+            RotabError::InvalidPrefixLen(33).to_string(),
+            "invalid prefix length: /33"
+        );
+        assert_eq!(
+            RotabError::InvertedRange {
+                start: Ipv4Addr::new(10, 0, 0, 255).into(),
+                end: Ipv4Addr::new(10, 0, 0, 0).into(),
+            }
+            .to_string(),
+            "range start 10.0.0.255 is greater than its end 10.0.0.0"
+        );
+        assert_eq!(
+            RotabError::NotSingleCidr {
+                start: Ipv4Addr::new(10, 0, 0, 1).into(),
+                end: Ipv4Addr::new(10, 0, 0, 6).into(),
+            }
+            .to_string(),
+            "range 10.0.0.1-10.0.0.6 is not a single CIDR block"
+        );
+        assert_eq!(
+            RotabError::NoRoute {
+                ip: Ipv4Addr::new(8, 8, 8, 8).into(),
+            }
+            .to_string(),
+            "no route to 8.8.8.8"
+        );
+        assert_eq!(
+            RotabError::LowerDistanceInstalled {
+                network: Ipv4Addr::new(10, 0, 0, 0).into(),
+                prefix_len: 24,
+                admin_distance: 20,
+            }
+            .to_string(),
+            "prefix 10.0.0.0/24 is installed with a lower admin distance of 20"
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{Address, ROOT, Table};
+
+/// How many lookups a route has answered, in a table made with
+/// `Table::new_counted`. Atomic so lookups can count through `&self`, and
+/// the table stays `Sync` for `SharedTable` readers.
+#[derive(Default)]
+pub(crate) struct HitCounter(AtomicUsize);
+
+impl HitCounter {
+    pub(crate) fn record(&self) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed) as u64
+    }
+}
+
+impl Clone for HitCounter {
+    fn clone(&self) -> Self {
+        Self(AtomicUsize::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+impl<V, A: Address> Table<V, A> {
+    /// Creates an empty table that counts how often each route is the
+    /// longest match of a lookup, to find hot and dead routes with
+    /// `hit_counts`. Counts wrap at `usize::MAX`, so on 32-bit targets after
+    /// about four billion hits.
+    ///
+    /// Lookups that forward an address count a hit for the route they
+    /// return: `lookup` and the lookups built on it (`lookup_required`,
+    /// `lookup_many`, `lookup_str`), `lookup_detailed`, `match_cidr`,
+    /// `lookup_with_meta`, `lookup_with_backup` (for the primary match
+    /// only), `lookup_u32`, `lookup_ecmp`, `lookup_first`, `lookup_failover`
+    /// and `lookup_weighted`. `lookup_bounded`,
+    /// `lookup_excluding_longer_than`, `lookup_trace`, `lookup_all` and
+    /// `contains_ip` don't, nor does a miss answered by the fallback.
+    pub fn new_counted() -> Self {
+        Self {
+            counted: true,
+            ..Self::new()
+        }
+    }
+
+    /// Returns every route's prefix with the number of lookups it answered,
+    /// in prefix order. Routes no lookup matched are included with a count
+    /// of zero, and all counts are zero unless the table was made with
+    /// `new_counted`. A replaced route keeps its count; a removed one
+    /// starts over if inserted again.
+    pub fn hit_counts(&self) -> Vec<((A, u8), u64)> {
+        self.nodes_from(ROOT)
+            .filter(|(_, node)| node.is_terminal)
+            .map(|(_, node)| {
+                (
+                    (A::from_bits(node.network), node.prefix_len),
+                    node.hits.get(),
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::{RouteTable, Table};
+
+    #[test]
+    fn test_hit_counts() {
+        let mut table = RouteTable::new_counted();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("172.16.0.0/12", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+
+        for i in 0..5 {
+            table.lookup(Ipv4Addr::new(10, 0, 1, i)).unwrap();
+        }
+        for i in 0..3 {
+            table.lookup(Ipv4Addr::new(10, 2, 0, i)).unwrap();
+        }
+        table.lookup_u32(0x0A00_0101);
+        table.lookup("8.8.8.8").unwrap();
+        // Listing covering routes isn't a match for any one of them.
+        table.lookup_all("10.0.1.1").unwrap();
+        // Nor are debugging and what-if lookups.
+        assert_eq!(
+            table.lookup_trace("10.0.1.1").unwrap().result,
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        table.lookup_bounded("10.0.1.1", 8).unwrap();
+        table.contains_ip("10.0.1.1").unwrap();
+
+        assert_eq!(
+            table.hit_counts(),
+            vec![
+                ((Ipv4Addr::new(10, 0, 0, 0), 8), 3),
+                ((Ipv4Addr::new(10, 0, 1, 0), 24), 6),
+                ((Ipv4Addr::new(172, 16, 0, 0), 12), 0),
+            ]
+        );
+
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 4))
+            .unwrap();
+        table.remove_cidr("10.0.0.0/8").unwrap();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_eq!(
+            table.hit_counts(),
+            vec![
+                ((Ipv4Addr::new(10, 0, 0, 0), 8), 0),
+                ((Ipv4Addr::new(10, 0, 1, 0), 24), 6),
+                ((Ipv4Addr::new(172, 16, 0, 0), 12), 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hit_counts_off_by_default() {
+        let mut table = Table::<u32>::new();
+        table.insert_cidr("10.0.0.0/8", 1).unwrap();
+        table.lookup("10.0.0.1").unwrap();
+
+        assert_eq!(
+            table.hit_counts(),
+            vec![((Ipv4Addr::new(10, 0, 0, 0), 8), 0)]
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_hit_counts_shared() {
+        use std::thread;
+
+        use crate::SharedTable;
+
+        let mut table = RouteTable::new_counted();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        let shared = SharedTable::from(table);
+
+        let readers = (0..4)
+            .map(|_| {
+                let shared = shared.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        shared.lookup("10.0.0.1").unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+
+        assert_eq!(
+            shared.read().hit_counts(),
+            vec![((Ipv4Addr::new(10, 0, 0, 0), 8), 400)]
+        );
+    }
+}
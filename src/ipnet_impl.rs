@@ -0,0 +1,105 @@
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+use ipnet::{Ipv4Net, Ipv6Net};
+
+use crate::{IntoAddr, RotabError, Table};
+
+impl<V> Table<V, Ipv4Addr> {
+    /// Inserts a route for `net`, masking off any host bits like
+    /// `insert_cidr`. Returns the destination it replaced, if any.
+    pub fn insert_net(&mut self, net: Ipv4Net, dest: V) -> Option<V> {
+        let net = net.trunc();
+
+        self.insert_prefix(net.network().to_bits(), net.prefix_len(), dest)
+    }
+
+    /// Like `match_cidr`, but returns the matched prefix as an `Ipv4Net`.
+    pub fn match_net(&self, ip: impl IntoAddr<Ipv4Addr>) -> Result<Option<(Ipv4Net, V)>, RotabError>
+    where
+        V: Clone,
+    {
+        Ok(self.lookup_detailed(ip)?.map(|m| {
+            let net = Ipv4Net::new(m.network, m.prefix_len).expect("prefix length within 32");
+            (net, m.dest)
+        }))
+    }
+}
+
+impl<V> Table<V, Ipv6Addr> {
+    /// Inserts a route for `net`, masking off any host bits like
+    /// `insert_cidr`. Returns the destination it replaced, if any.
+    pub fn insert_net(&mut self, net: Ipv6Net, dest: V) -> Option<V> {
+        let net = net.trunc();
+
+        self.insert_prefix(net.network().to_bits(), net.prefix_len(), dest)
+    }
+
+    /// Like `match_cidr`, but returns the matched prefix as an `Ipv6Net`.
+    pub fn match_net(&self, ip: impl IntoAddr<Ipv6Addr>) -> Result<Option<(Ipv6Net, V)>, RotabError>
+    where
+        V: Clone,
+    {
+        Ok(self.lookup_detailed(ip)?.map(|m| {
+            let net = Ipv6Net::new(m.network, m.prefix_len).expect("prefix length within 128");
+            (net, m.dest)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, Ipv6Addr},
+        str::FromStr,
+    };
+
+    use ipnet::{Ipv4Net, Ipv6Net};
+
+    use crate::{RouteTable, Table6};
+
+    #[test]
+    fn test_insert_and_match_net() {
+        let mut table = RouteTable::new();
+        let net = Ipv4Net::new(Ipv4Addr::new(10, 0, 1, 0), 24).unwrap();
+        assert_eq!(table.insert_net(net, Ipv4Addr::new(192, 168, 0, 1)), None);
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(
+            table.match_net("10.0.1.7").unwrap(),
+            Some((net, Ipv4Addr::new(192, 168, 0, 1)))
+        );
+        assert_eq!(
+            table.match_net(Ipv4Addr::new(10, 9, 0, 1)).unwrap(),
+            Some((
+                Ipv4Net::from_str("10.0.0.0/8").unwrap(),
+                Ipv4Addr::new(192, 168, 0, 2)
+            ))
+        );
+        assert_eq!(table.match_net("8.8.8.8").unwrap(), None);
+
+        // Host bits are masked off, so this replaces the /24 route.
+        let unmasked = Ipv4Net::from_str("10.0.1.99/24").unwrap();
+        assert_eq!(
+            table.insert_net(unmasked, Ipv4Addr::new(192, 168, 0, 3)),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn test_insert_and_match_net6() {
+        let mut table = Table6::new();
+        let net = Ipv6Net::from_str("2001:db8::/32").unwrap();
+        table.insert_net(net, "transit");
+
+        assert_eq!(
+            table
+                .match_net(Ipv6Addr::from_str("2001:db8::1").unwrap())
+                .unwrap(),
+            Some((net, "transit"))
+        );
+        assert_eq!(table.match_net("2001:db9::1").unwrap(), None);
+    }
+}
@@ -1,542 +1,6849 @@
-use std::{
-    cell::RefCell,
-    net::{AddrParseError, Ipv4Addr},
-    ops::{BitAnd, BitXor},
-    rc::Rc,
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+mod action;
+mod addr;
+mod aggregate;
+mod builder;
+mod bytes;
+mod cache;
+mod entry;
+mod error;
+mod hits;
+#[cfg(feature = "ipnet")]
+mod ipnet_impl;
+mod meta;
+#[cfg(feature = "serde")]
+mod serde_impl;
+#[cfg(feature = "std")]
+mod shared;
+mod snapshot;
+mod vrf;
+
+use alloc::{
+    borrow::ToOwned,
+    collections::BTreeMap,
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{
+    fmt,
+    marker::PhantomData,
+    net::{Ipv4Addr, Ipv6Addr},
+    ops::BitXor,
     str::FromStr,
 };
 
-pub struct Node {
-    edges: [Option<Rc<RefCell<Node>>>; 2],
+use aggregate::AutoAggregate;
+use hits::HitCounter;
+
+pub use action::RouteAction;
+pub use addr::{Address, Bits, IntoAddr, bits_to_ipv4, parse_lenient};
+use addr::{bit_at, mask};
+pub use builder::TableBuilder;
+pub use cache::CachedTable;
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use error::{LoadError, RotabError};
+pub use meta::{RouteMeta, RouteSource};
+#[cfg(feature = "std")]
+pub use shared::SharedTable;
+pub use snapshot::TableSnapshot;
+pub use vrf::VrfTable;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<V, A = Ipv4Addr> {
+    pub dest: V,
+    pub prefix_len: u8,
+    pub network: A,
+}
+
+/// The path a lookup took through the trie, from `Table::lookup_trace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LookupTrace<V> {
+    /// The bits of the address the walk matched, most significant first,
+    /// up to the deepest node that still covered it.
+    pub bits_consumed: Vec<u8>,
+    /// The prefix length and destination of every route passed on the way.
+    pub terminals_seen: Vec<(u8, V)>,
+    /// What `lookup` returns for the address.
+    pub result: Option<V>,
+}
+
+/// How `Table::merge` handles a prefix installed in both tables.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    KeepExisting,
+    Overwrite,
+    /// Fail with `RotabError::MergeConflict`, leaving the table unchanged.
+    Error,
+}
+
+/// What `Table::insert_with_outcome` did to the prefix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertOutcome<V> {
+    /// The prefix wasn't installed before.
+    Created,
+    /// The prefix was already installed with the same destination, so the
+    /// table was left as it was.
+    Unchanged,
+    /// The prefix was installed with another destination, which is returned.
+    Updated(V),
+}
+
+/// The prefixes that differ between two tables, from `Table::diff`, each in
+/// prefix order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDiff<A = Ipv4Addr> {
+    /// Prefixes only in the other table.
+    pub added: Vec<(A, u8)>,
+    /// Prefixes only in this table.
+    pub removed: Vec<(A, u8)>,
+    /// Prefixes in both tables with different destinations.
+    pub changed: Vec<(A, u8)>,
+}
+
+/// How two tables handle a range differently, from `Table::coverage_diff`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoverageState<V> {
+    /// Only this table routes the range.
+    OnlyThis(V),
+    /// Only the other table routes the range.
+    OnlyOther(V),
+    /// Both tables route the range, to different destinations.
+    Differs { this: V, other: V },
+}
+
+/// Size and shape of a table's trie, as reported by `Table::stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TableStats {
+    pub node_count: usize,
+    pub route_count: usize,
+    /// Number of edges on the longest path from the root.
+    pub max_depth: usize,
+    pub estimated_bytes: usize,
+}
+
+/// A node of the path-compressed trie. Each node keeps the full prefix it
+/// represents, so an edge can skip every bit between its parent's
+/// `prefix_len` and its own.
+#[derive(Clone)]
+struct Node<V, B> {
+    edges: [Option<u32>; 2],
+    network: B,
+    prefix_len: u8,
     is_terminal: bool,
-    dest: Option<Ipv4Addr>,
+    dest: Option<V>,
+    meta: Option<RouteMeta>,
+    hits: HitCounter,
+}
+
+/// A route as `(network, prefix_len, node)`, where the node holds its
+/// destination and metadata. In a table made with
+/// `Table::new_auto_aggregate`, that may be the node of the route it was
+/// merged into.
+type RouteRef<'a, V, B> = (B, u8, &'a Node<V, B>);
+
+/// Picks what a lookup returns among the routes covering an address, given
+/// from least to most specific: the longest, unless a route marked
+/// `preferred` is among them, in which case the longest preferred one.
+fn select_preferred<T: Copy>(
+    routes: impl IntoIterator<Item = T>,
+    meta: impl Fn(&T) -> Option<RouteMeta>,
+) -> Option<T> {
+    let mut longest = None;
+    let mut preferred = None;
+    for route in routes {
+        if meta(&route).is_some_and(|meta| meta.preferred) {
+            preferred = Some(route);
+        }
+        longest = Some(route);
+    }
+
+    preferred.or(longest)
 }
 
-impl Node {
-    fn new() -> Self {
+impl<V, B: Bits> Node<V, B> {
+    fn new(network: B, prefix_len: u8) -> Self {
         Self {
             edges: [None, None],
+            network,
+            prefix_len,
             dest: None,
+            meta: None,
             is_terminal: false,
+            hits: HitCounter::default(),
         }
     }
+
+    fn matches(&self, network: B, prefix_len: u8) -> bool {
+        self.prefix_len <= prefix_len && network & mask(self.prefix_len) == self.network
+    }
+
+    fn children(&self) -> impl Iterator<Item = u32> {
+        self.edges.into_iter().flatten()
+    }
+}
+
+/// Walks a subtree of the trie in pre-order, lower edge first, yielding
+/// each node with its index. The nodes under a node come right after it, so
+/// routes come out in prefix order.
+struct Nodes<'a, V, B> {
+    nodes: &'a [Node<V, B>],
+    stack: Vec<u32>,
+}
+
+impl<'a, V, B> Iterator for Nodes<'a, V, B> {
+    type Item = (u32, &'a Node<V, B>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.stack.pop()?;
+        let node = &self.nodes[idx as usize];
+        self.stack.extend(node.edges.into_iter().rev().flatten());
+
+        Some((idx, node))
+    }
+}
+
+/// The outermost routes among `nodes`, given in the order of `Nodes`: those
+/// not inside another of them.
+fn outermost<'a, V: 'a, B: Bits + 'a>(
+    nodes: impl Iterator<Item = &'a Node<V, B>>,
+) -> impl Iterator<Item = &'a Node<V, B>> {
+    let mut outer: Option<&Node<V, B>> = None;
+    nodes.filter(move |node| {
+        if !node.is_terminal
+            || outer.is_some_and(|outer| outer.matches(node.network, node.prefix_len))
+        {
+            return false;
+        }
+        outer = Some(node);
+        true
+    })
 }
 
-pub struct Table {
-    start: Rc<RefCell<Node>>,
+/// A routing table mapping prefixes of `A` addresses to `V` payloads, with
+/// longest-prefix-match lookups. The trie's nodes are private, so its
+/// layout can change without breaking callers:
+///
+/// ```compile_fail
+/// use rotab::Node;
+/// ```
+#[derive(Clone)]
+pub struct Table<V, A: Address = Ipv4Addr> {
+    nodes: Vec<Node<V, A::Bits>>,
+    free: Vec<u32>,
+    len: usize,
+    max_prefix_len: u8,
+    /// Number of routes of each prefix length, so `max_prefix_len` can be
+    /// kept up to date without rescanning the routes.
+    prefix_len_counts: Vec<usize>,
+    /// Set by `new_auto_aggregate`.
+    auto_aggregate: Option<AutoAggregate<V, A::Bits>>,
+    fallback: Option<V>,
+    /// Set by `new_counted`, to count each route's lookups.
+    counted: bool,
+    generation: u64,
+}
+
+const ROOT: u32 = 0;
+
+pub type RouteTable = Table<Ipv4Addr>;
+
+pub type EcmpTable = Table<Vec<Ipv4Addr>>;
+
+pub type Table6<V> = Table<V, Ipv6Addr>;
+
+pub type WeightedTable = Table<Vec<(Ipv4Addr, u16)>>;
+
+pub type ActionTable = Table<RouteAction>;
+
+impl<V, A: Address> Default for Table<V, A> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
-impl Table {
+impl<V, A: Address> Table<V, A> {
     pub fn new() -> Self {
         Self {
-            start: Rc::new(RefCell::new(Node::new())),
+            nodes: vec![Node::new(A::Bits::ZERO, 0)],
+            free: vec![],
+            len: 0,
+            max_prefix_len: 0,
+            prefix_len_counts: vec![0; A::BITS as usize + 1],
+            auto_aggregate: None,
+            fallback: None,
+            counted: false,
+            generation: 0,
+        }
+    }
+
+    /// Creates an empty table whose `lookup` returns `dest` for addresses no
+    /// route covers, as do all other lookups that return only a destination,
+    /// e.g. `lookup_u32`, `lookup_bounded` and `lookup_with_meta` (with no
+    /// metadata), and `contains_ip` counts it as covering every address.
+    /// Unlike a `0.0.0.0/0` route, the fallback isn't a route: it isn't
+    /// counted by `len` or listed by `routes`, lookups that report the
+    /// matched prefix, like `lookup_detailed` and `lookup_all`, don't return
+    /// it, and an installed /0 route takes precedence over it.
+    pub fn with_default(dest: V) -> Self {
+        Self {
+            fallback: Some(dest),
+            ..Self::new()
         }
     }
 
-    fn ip_to_bit_vec(ip: String) -> Result<Vec<u8>, AddrParseError> {
-        let ip = Ipv4Addr::from_str(&ip)?.to_bits();
+    /// Returns the fallback set by `with_default`, if any.
+    pub fn fallback(&self) -> Option<&V> {
+        self.fallback.as_ref()
+    }
 
-        let size = (size_of::<u32>() * 8) as u32;
+    /// Creates an empty table with room for at least `routes` routes. The
+    /// hint counts routes, not trie nodes: each route needs at most two
+    /// nodes, so the arena reserves twice as many slots and inserting that
+    /// many routes never reallocates it.
+    pub fn with_capacity(routes: usize) -> Self {
+        let mut nodes = Vec::with_capacity(2 * routes + 1);
+        nodes.push(Node::new(A::Bits::ZERO, 0));
 
-        let mut bit_vec = vec![];
-        for i in 0..size {
-            let pow = size - i - 1;
-            let b = (ip).bitand(2_u32.pow(pow)) >> pow;
-            bit_vec.push(b as u8);
+        Self {
+            nodes,
+            ..Self::new()
         }
+    }
+
+    /// Returns the number of routes an empty table could hold without
+    /// reallocating its node arena, counted the same way as the hint to
+    /// `with_capacity`.
+    pub fn capacity(&self) -> usize {
+        (self.nodes.capacity() - 1) / 2
+    }
+
+    /// Reserves room for at least `additional` more routes ahead of a bulk
+    /// insert, like `Vec::reserve`. As with `with_capacity`, each route is
+    /// counted as two nodes; slots freed by removals are reused first.
+    pub fn reserve(&mut self, additional: usize) {
+        self.nodes
+            .reserve((2 * additional).saturating_sub(self.free.len()));
+    }
+
+    /// Returns the number of nodes the arena holds without reallocating,
+    /// including the root and slots in use.
+    pub fn node_capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    /// Returns the number of distinct prefixes in the table.
+    pub fn len(&self) -> usize {
+        self.len
+    }
 
-        Ok(bit_vec)
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 
-    fn prefix(&self, start: String, end: String) -> Result<Vec<u8>, AddrParseError> {
-        let start_ip_bits = Ipv4Addr::from_str(&start)?.to_bits();
-        let end_ip_bits = Ipv4Addr::from_str(&end)?.to_bits();
+    /// Returns the length of the most specific prefix installed, or 0 for an
+    /// empty table. Kept up to date on insert and remove, so it's a cheap
+    /// depth to pass to `lookup_bounded`.
+    pub fn max_prefix_len(&self) -> u8 {
+        self.max_prefix_len
+    }
+
+    /// Returns a counter that goes up whenever the routes may have changed:
+    /// on every insert, removal of an existing route, `clear`, `restore`,
+    /// and mutable access to destinations through `for_each_mut` or an
+    /// entry. Lookups and `compact` leave it alone, so a cache that saw the
+    /// same generation is still valid.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Counts the routes of each prefix length: entry `i` is the number of
+    /// `/i` routes, from `/0` up to the address width.
+    pub fn prefix_len_histogram(&self) -> Vec<usize> {
+        self.prefix_len_counts.clone()
+    }
+
+    /// Counts a new route of `prefix_len` in `len`, the per-length counts
+    /// and `max_prefix_len`.
+    fn count_route(&mut self, prefix_len: u8) {
+        self.len += 1;
+        self.prefix_len_counts[prefix_len as usize] += 1;
+        self.max_prefix_len = self.max_prefix_len.max(prefix_len);
+    }
+
+    /// Undoes `count_route` for a removed route. If it was the last route of
+    /// the maximum length, the next length down with a route takes over,
+    /// which takes at most `A::BITS` steps however many routes there are.
+    fn uncount_route(&mut self, prefix_len: u8) {
+        self.len -= 1;
+        self.prefix_len_counts[prefix_len as usize] -= 1;
+        while self.max_prefix_len > 0 && self.prefix_len_counts[self.max_prefix_len as usize] == 0 {
+            self.max_prefix_len -= 1;
+        }
+    }
 
-        let prefix_length = start_ip_bits.bitxor(end_ip_bits).leading_zeros();
+    pub fn stats(&self) -> TableStats {
+        let mut node_count = 0;
+        let mut max_depth = 0;
+
+        // The nodes above the current one; a node's depth is their number.
+        let mut path: Vec<&Node<V, A::Bits>> = vec![];
+        for (_, node) in self.nodes_from(ROOT) {
+            while path
+                .last()
+                .is_some_and(|parent| !parent.matches(node.network, node.prefix_len))
+            {
+                path.pop();
+            }
 
-        let mut prefix = vec![];
-        for i in 0..prefix_length {
-            let pow = (size_of::<u32>() * 8) as u32 - i - 1;
-            let b = (start_ip_bits).bitand(2_u32.pow(pow)) >> pow;
-            prefix.push(b as u8);
+            node_count += 1;
+            max_depth = max_depth.max(path.len());
+            path.push(node);
         }
 
-        Ok(prefix)
+        TableStats {
+            node_count,
+            route_count: self.len,
+            max_depth,
+            estimated_bytes: size_of::<Self>()
+                + self.nodes.capacity() * size_of::<Node<V, A::Bits>>()
+                + self.free.capacity() * size_of::<u32>(),
+        }
     }
 
-    pub fn insert_range(
-        &mut self,
-        start: String,
-        end: String,
-        dest: String,
-    ) -> Result<(), AddrParseError> {
-        let prefix = self.prefix(start, end)?;
+    /// Checks the trie's structure, for tests of code that reshapes it.
+    /// Every node other than the root must hold a route or branch both
+    /// ways, each edge must lead to a longer prefix under its parent on the
+    /// side of the edge's bit, and the route count, the counts per prefix
+    /// length, the maximum prefix length and the free slots must match the
+    /// nodes. In a table made with `new_auto_aggregate`, the routes hidden
+    /// by merges must also be under the route they were merged into.
+    /// Returns a description of the first violation found.
+    pub fn validate(&self) -> Result<(), String> {
+        let prefix =
+            |node: &Node<V, A::Bits>| format!("{}/{}", A::from_bits(node.network), node.prefix_len);
+
+        let root = self.node(ROOT);
+        if root.network != A::Bits::ZERO || root.prefix_len != 0 {
+            return Err(format!("root holds {} instead of /0", prefix(root)));
+        }
 
-        let mut curr_node = Rc::clone(&self.start);
+        let mut seen = vec![false; self.nodes.len()];
+        let mut terminals = 0;
+        let mut max_prefix_len = 0;
+        let mut prefix_len_counts = vec![0; A::BITS as usize + 1];
 
-        for bit in prefix {
-            let node = Rc::clone(&curr_node);
-            let mut node = node.borrow_mut();
-            let bit_idx = bit as usize;
+        // The walk only reaches a node once the edge to it has been checked.
+        for (idx, node) in self.nodes_from(ROOT) {
+            if core::mem::replace(&mut seen[idx as usize], true) {
+                return Err(format!("node {idx} ({}) is reached twice", prefix(node)));
+            }
+            if self.free.contains(&idx) {
+                return Err(format!("node {idx} ({}) is in use but free", prefix(node)));
+            }
 
-            if let Some(next) = &node.edges[bit_idx] {
-                curr_node = Rc::clone(next);
-            } else {
-                let next_node = Rc::new(RefCell::new(Node::new()));
-                node.edges[bit_idx] = Some(Rc::clone(&next_node));
+            if node.network & !mask::<A::Bits>(node.prefix_len) != A::Bits::ZERO {
+                return Err(format!("{} has host bits set", prefix(node)));
+            }
+            if node.is_terminal != node.dest.is_some() {
+                return Err(format!(
+                    "{} is terminal without a destination",
+                    prefix(node)
+                ));
+            }
+            if !node.is_terminal && node.meta.is_some() {
+                return Err(format!("{} has metadata but no route", prefix(node)));
+            }
+            if idx != ROOT && !node.is_terminal && node.children().count() < 2 {
+                return Err(format!("{} has no route and doesn't branch", prefix(node)));
+            }
 
-                curr_node = next_node;
+            if node.is_terminal {
+                terminals += 1;
+                max_prefix_len = max_prefix_len.max(node.prefix_len);
+                prefix_len_counts[node.prefix_len as usize] += 1;
+            }
+
+            for (bit, next) in node.edges.into_iter().enumerate() {
+                let Some(next) = next else { continue };
+                let child = self.nodes.get(next as usize).ok_or_else(|| {
+                    format!("edge to node {next} is past the {} nodes", self.nodes.len())
+                })?;
+                if !(child.prefix_len > node.prefix_len
+                    && node.matches(child.network, child.prefix_len)
+                    && bit_at(child.network, node.prefix_len) == bit)
+                {
+                    return Err(format!(
+                        "{} is on edge {bit} under {}",
+                        prefix(child),
+                        prefix(node)
+                    ));
+                }
             }
         }
 
-        let mut node = curr_node.borrow_mut();
-        node.dest = Some(Ipv4Addr::from_str(&dest)?);
-        node.is_terminal = true;
+        if terminals != self.len {
+            return Err(format!("len is {} but {terminals} routes exist", self.len));
+        }
+        if let Some(len) = (0..=A::BITS as usize)
+            .find(|&len| prefix_len_counts[len] != self.prefix_len_counts[len])
+        {
+            return Err(format!(
+                "{} routes of /{len} are counted but {} exist",
+                self.prefix_len_counts[len], prefix_len_counts[len]
+            ));
+        }
+        if max_prefix_len != self.max_prefix_len {
+            return Err(format!(
+                "max_prefix_len is /{} but the longest route is /{max_prefix_len}",
+                self.max_prefix_len
+            ));
+        }
+        let reachable = seen.iter().filter(|&&seen| seen).count();
+        if reachable + self.free.len() != self.nodes.len() {
+            return Err(format!(
+                "{reachable} nodes are reachable and {} free, out of {}",
+                self.free.len(),
+                self.nodes.len()
+            ));
+        }
 
-        Ok(())
+        self.validate_merges()
     }
 
-    pub fn lookup(&self, ip: String) -> Result<Option<Ipv4Addr>, AddrParseError> {
-        let ip = Table::ip_to_bit_vec(ip)?;
+    /// Removes every route, keeping the allocated node storage.
+    pub fn clear(&mut self) {
+        self.nodes.truncate(1);
+        self.nodes[ROOT as usize] = Node::new(A::Bits::ZERO, 0);
+        self.free.clear();
+        self.len = 0;
+        self.max_prefix_len = 0;
+        self.prefix_len_counts.fill(0);
+        if let Some(auto) = &mut self.auto_aggregate {
+            auto.clear();
+        }
+        self.generation += 1;
+    }
 
-        let mut dst = {
-            let n = self.start.borrow();
-            if n.is_terminal { n.dest } else { None }
-        };
+    /// Rebuilds the node arena without the slots freed by removals, and
+    /// releases the spare capacity. Returns the number of slots reclaimed.
+    pub fn compact(&mut self) -> usize {
+        let mut order = vec![];
+        let mut remap = vec![None; self.nodes.len()];
 
-        let mut curr_node = Rc::clone(&self.start);
+        for (idx, _) in self.nodes_from(ROOT) {
+            remap[idx as usize] = Some(order.len() as u32);
+            order.push(idx);
+        }
 
-        for bit in ip {
-            let node = Rc::clone(&curr_node);
-            let bit_idx = bit as usize;
+        let reclaimed = self.nodes.len() - order.len();
 
-            if let Some(next) = &node.borrow().edges[bit_idx] {
-                curr_node = Rc::clone(next);
+        let mut old = core::mem::take(&mut self.nodes)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.nodes = order
+            .into_iter()
+            .filter_map(|idx| old[idx as usize].take())
+            .map(|mut node| {
+                node.edges = node.edges.map(|edge| edge.and_then(|e| remap[e as usize]));
+                node
+            })
+            .collect();
+        self.nodes.shrink_to_fit();
+        self.free = vec![];
 
-                let next = next.borrow();
-                if next.is_terminal {
-                    dst = next.dest;
-                }
-            } else {
+        reclaimed
+    }
+
+    fn node(&self, idx: u32) -> &Node<V, A::Bits> {
+        &self.nodes[idx as usize]
+    }
+
+    fn nodes_from(&self, start: u32) -> Nodes<'_, V, A::Bits> {
+        Nodes {
+            nodes: &self.nodes,
+            stack: vec![start],
+        }
+    }
+
+    /// The nodes at or inside the prefix, in the order of `nodes_from`.
+    fn nodes_within(
+        &self,
+        network: A::Bits,
+        prefix_len: u8,
+    ) -> impl Iterator<Item = &Node<V, A::Bits>> {
+        self.subtree(network, prefix_len)
+            .into_iter()
+            .flat_map(|idx| self.nodes_from(idx))
+            .map(|(_, node)| node)
+    }
+
+    fn node_mut(&mut self, idx: u32) -> &mut Node<V, A::Bits> {
+        &mut self.nodes[idx as usize]
+    }
+
+    fn alloc_node(&mut self, network: A::Bits, prefix_len: u8) -> u32 {
+        if let Some(idx) = self.free.pop() {
+            *self.node_mut(idx) = Node::new(network, prefix_len);
+            idx
+        } else {
+            self.nodes.push(Node::new(network, prefix_len));
+            (self.nodes.len() - 1) as u32
+        }
+    }
+
+    /// Returns the longest prefix shared by `start` and `end`, as the
+    /// network's integer value and the prefix length. This is the smallest
+    /// single prefix containing the whole range, e.g. `(0x0A00_0000, 23)`
+    /// for `10.0.0.0` and `10.0.1.255`.
+    pub fn common_prefix(start: A, end: A) -> (A::Bits, u8) {
+        Self::prefix(start.to_bits(), end.to_bits())
+    }
+
+    /// Returns the smallest prefix containing every address in `addrs`, to
+    /// summarize a group of hosts as one route: `10.0.0.0/28` for
+    /// `10.0.0.1`, `10.0.0.5` and `10.0.0.9`. Returns `None` if `addrs` is
+    /// empty.
+    pub fn supernet_of(addrs: &[A]) -> Option<(A, u8)> {
+        // The prefix shared by the lowest and highest address is shared by
+        // everything between them.
+        let lowest = addrs.iter().min()?.to_bits();
+        let highest = addrs.iter().max()?.to_bits();
+        let (network, prefix_len) = Self::prefix(lowest, highest);
+
+        Some((A::from_bits(network), prefix_len))
+    }
+
+    fn prefix(start: A::Bits, end: A::Bits) -> (A::Bits, u8) {
+        let prefix_len = start.bitxor(end).leading_zeros() as u8;
+
+        (start & mask(prefix_len), prefix_len)
+    }
+
+    /// Splits `start..=end` into the minimal set of aligned CIDR blocks,
+    /// returned as `(first, last)` address pairs.
+    fn range_to_blocks(start: A::Bits, end: A::Bits) -> Vec<(A::Bits, A::Bits)> {
+        // A single host is its own block; skip narrowing it down from the
+        // alignment of `start`, which takes up to `A::BITS` steps.
+        if start == end {
+            return vec![(start, end)];
+        }
+
+        let mut blocks = vec![];
+
+        let mut curr = start;
+        loop {
+            // `curr` is aligned to `host_bits`, so the block's last address
+            // is found by setting the host bits rather than by adding (which
+            // could overflow at the top of the address space).
+            let mut host_bits = curr.trailing_zeros().min(A::BITS);
+            while curr | !mask::<A::Bits>((A::BITS - host_bits) as u8) > end {
+                host_bits -= 1;
+            }
+
+            let last = curr | !mask::<A::Bits>((A::BITS - host_bits) as u8);
+            blocks.push((curr, last));
+
+            if last == end {
                 break;
             }
+            curr = last + A::Bits::ONE;
         }
 
-        Ok(dst)
+        blocks
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub(crate) fn parse_range(
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<(A::Bits, A::Bits), RotabError> {
+        let start = start.into_addr()?;
+        let end = end.into_addr()?;
+        if start > end {
+            return Err(RotabError::InvertedRange {
+                start: start.into(),
+                end: end.into(),
+            });
+        }
 
-    fn create_table() -> Table {
-        Table::new()
+        Ok((start.to_bits(), end.to_bits()))
     }
 
-    #[test]
-    fn ip_to_bit_vec() {
-        let test_cases = vec![
-            (
-                "192.168.0.1",
-                vec![
-                    1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 1,
-                ],
-            ),
-            ("0.0.0.0", vec![0; 32]),
-            ("255.255.255.255", vec![1; 32]),
-            (
-                "128.0.0.0",
-                vec![
-                    1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0,
-                ],
-            ),
-            (
-                "0.0.0.1",
-                vec![
-                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 1,
-                ],
-            ),
-            (
-                "10.0.0.0",
-                vec![
-                    0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 0,
-                ],
-            ),
-            (
-                "127.0.0.1",
-                vec![
-                    0, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-                    0, 0, 0, 0, 0, 1,
-                ],
-            ),
-            (
-                "1.2.3.4",
-                vec![
-                    0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0,
-                    0, 0, 0, 1, 0, 0,
-                ],
-            ),
-        ];
+    /// Like `parse_range`, but requires `start..=end` to be exactly one CIDR
+    /// block, returned as `(network, prefix_len)`.
+    fn parse_block(
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<(A::Bits, u8), RotabError> {
+        let (start, end) = Self::parse_range(start, end)?;
 
-        for (ip, expected) in test_cases {
-            let res = Table::ip_to_bit_vec(ip.to_owned()).unwrap();
-            assert_eq!(res.len(), 32);
-            assert_eq!(res, expected);
+        let (network, prefix_len) = Self::prefix(start, end);
+        if network != start || network | !mask::<A::Bits>(prefix_len) != end {
+            return Err(RotabError::NotSingleCidr {
+                start: A::from_bits(start).into(),
+                end: A::from_bits(end).into(),
+            });
         }
+
+        Ok((network, prefix_len))
     }
 
-    #[test]
-    fn prefix_length() {
-        let test_cases = vec![
-            (
-                "192.168.1.1",
-                "192.168.1.1",
-                32,
-                "11000000101010000000000100000001",
-            ),
-            (
-                "192.168.0.0",
-                "192.168.0.255",
-                24,
-                "110000001010100000000000",
-            ),
-            ("10.0.0.0", "10.0.0.255", 24, "000010100000000000000000"),
-            (
-                "172.16.0.0",
-                "172.16.0.127",
-                25,
-                "1010110000010000000000000",
-            ),
-            (
-                "192.168.1.0",
-                "192.168.1.127",
-                25,
-                "1100000010101000000000010",
-            ),
-            ("10.1.0.0", "10.1.255.255", 16, "0000101000000001"),
-            (
-                "172.20.10.0",
-                "172.20.10.31",
-                27,
-                "101011000001010000001010000",
-            ),
-            (
-                "192.168.100.0",
-                "192.168.100.63",
-                26,
-                "11000000101010000110010000",
-            ),
-            ("10.10.0.0", "10.10.31.255", 19, "0000101000001010000"),
-            ("172.31.0.0", "172.31.15.255", 20, "10101100000111110000"),
-            (
-                "192.168.50.0",
-                "192.168.50.15",
-                28,
-                "1100000010101000001100100000",
-            ),
-            (
-                "192.168.1.1",
-                "192.168.1.1",
-                32,
-                "11000000101010000000000100000001",
-            ),
-            (
-                "192.168.2.0",
-                "192.168.2.1",
-                31,
-                "1100000010101000000000100000000",
-            ),
-            (
-                "192.168.3.0",
-                "192.168.3.3",
-                30,
-                "110000001010100000000011000000",
-            ),
-            (
-                "192.168.255.0",
-                "192.168.255.255",
-                24,
-                "110000001010100011111111",
-            ),
-            (
-                "192.168.4.0",
-                "192.168.4.7",
-                29,
-                "11000000101010000000010000000",
-            ),
-            (
-                "192.168.5.0",
-                "192.168.5.15",
-                28,
-                "1100000010101000000001010000",
-            ),
-            ("172.20.0.0", "172.20.255.255", 16, "1010110000010100"),
-            ("10.20.0.0", "10.20.1.255", 23, "00001010000101000000000"),
-            ("172.30.0.0", "172.30.3.255", 22, "1010110000011110000000"),
-            ("10.30.0.0", "10.30.7.255", 21, "000010100001111000000"),
-            ("0.0.0.0", "255.255.255.255", 0, ""),
-        ];
+    /// Inserts a route for `start..=end`. Returns the previous destination
+    /// if one of the range's prefixes was already installed.
+    ///
+    /// Admin distance only arbitrates between inserts with metadata: this
+    /// replaces an installed route whatever its distance, and drops its
+    /// metadata.
+    pub fn insert_range(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        self.insert_range_inner(start, end, dest, None)
+    }
 
-        let table = create_table();
+    /// Like `insert_range`, but also stores `meta` with the route.
+    ///
+    /// Fails with `LowerDistanceInstalled`, leaving the table unchanged, if
+    /// any of the range's prefixes is already installed with a lower admin
+    /// distance. Ties go to the new route.
+    pub fn insert_range_with_meta(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+        meta: RouteMeta,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        self.insert_range_inner(start, end, dest, Some(meta))
+    }
 
-        for case in test_cases {
-            let prefix = table.prefix(case.0.to_owned(), case.1.to_owned()).unwrap();
-            assert_eq!(prefix.len(), case.2);
+    fn insert_range_inner(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+        meta: Option<RouteMeta>,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        let (start, end) = Self::parse_range(start, end)?;
+        let blocks = Self::range_to_blocks(start, end)
+            .into_iter()
+            .map(|(first, last)| Self::prefix(first, last))
+            .collect::<Vec<_>>();
 
-            let prefix_str = prefix
-                .into_iter()
-                .map(|p| p.to_string())
-                .collect::<Vec<String>>()
-                .concat();
-            assert_eq!(prefix_str, case.3)
+        if let Some(meta) = meta {
+            for &(network, prefix_len) in &blocks {
+                if let Some(admin_distance) = self.preferred_distance(network, prefix_len, meta) {
+                    return Err(RotabError::LowerDistanceInstalled {
+                        network: A::from_bits(network).into(),
+                        prefix_len,
+                        admin_distance,
+                    });
+                }
+            }
+        }
+
+        let mut old = None;
+        for (network, prefix_len) in blocks {
+            let prev = self.insert_prefix_with_meta(network, prefix_len, dest.clone(), meta);
+            old = old.or(prev);
+        }
+
+        Ok(old)
+    }
+
+    /// Returns the admin distance of the installed route for the prefix if
+    /// it's lower than that of `meta`.
+    fn preferred_distance(
+        &mut self,
+        network: A::Bits,
+        prefix_len: u8,
+        meta: RouteMeta,
+    ) -> Option<u8> {
+        // A route merged by `new_auto_aggregate` keeps its own metadata.
+        self.unmerge(network, prefix_len);
+        let installed = self
+            .find_route(network, prefix_len)
+            .and_then(|node| node.meta)
+            .map(|installed| installed.admin_distance)
+            .filter(|&admin_distance| admin_distance < meta.admin_distance);
+        self.merge_at(network, prefix_len);
+
+        installed
+    }
+
+    /// Inserts a route for the parts of `start..=end` that no installed route
+    /// of the same or a longer prefix already handles, e.g. a fallback for
+    /// `10.0.0.0/8` that leaves an existing `10.0.1.0/24` alone. Returns the
+    /// number of prefixes inserted.
+    pub fn insert_range_exclusive(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<usize, RotabError>
+    where
+        V: Clone,
+    {
+        let (start, end) = Self::parse_range(start, end)?;
+
+        let mut gaps = vec![];
+        for (first, last) in Self::range_to_blocks(start, end) {
+            let (network, prefix_len) = Self::prefix(first, last);
+
+            let mut next = Some(first);
+            for (covered_first, covered_last) in self.outermost_routes_within(network, prefix_len) {
+                if let Some(curr) = next
+                    && curr < covered_first
+                {
+                    gaps.push((curr, covered_first - A::Bits::ONE));
+                }
+                next = (covered_last != last).then(|| covered_last + A::Bits::ONE);
+            }
+            if let Some(curr) = next {
+                gaps.push((curr, last));
+            }
+        }
+
+        let mut inserted = 0;
+        for (first, last) in gaps {
+            for (first, last) in Self::range_to_blocks(first, last) {
+                let (network, prefix_len) = Self::prefix(first, last);
+                self.insert_prefix(network, prefix_len, dest.clone());
+                inserted += 1;
+            }
+        }
+
+        Ok(inserted)
+    }
+
+    /// Returns the `(first, last)` ranges of the outermost routes at or
+    /// inside the prefix, in address order.
+    fn outermost_routes_within(&self, network: A::Bits, prefix_len: u8) -> Vec<(A::Bits, A::Bits)> {
+        outermost(self.nodes_within(network, prefix_len))
+            .map(|node| {
+                (
+                    node.network,
+                    node.network | !mask::<A::Bits>(node.prefix_len),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the topmost node at or inside the prefix.
+    fn subtree(&self, network: A::Bits, prefix_len: u8) -> Option<u32> {
+        let mut curr_node = ROOT;
+
+        loop {
+            let node = self.node(curr_node);
+            if node.prefix_len >= prefix_len {
+                return (node.network & mask(prefix_len) == network).then_some(curr_node);
+            }
+            if !node.matches(network, prefix_len) {
+                return None;
+            }
+
+            curr_node = node.edges[bit_at(network, node.prefix_len)]?;
+        }
+    }
+
+    fn insert_prefix(&mut self, network: A::Bits, prefix_len: u8, dest: V) -> Option<V> {
+        self.insert_prefix_with_meta(network, prefix_len, dest, None)
+    }
+
+    fn insert_prefix_with_meta(
+        &mut self,
+        network: A::Bits,
+        prefix_len: u8,
+        dest: V,
+        meta: Option<RouteMeta>,
+    ) -> Option<V> {
+        self.unmerge(network, prefix_len);
+        let old = self.install_prefix(network, prefix_len, dest, meta);
+        self.merge_at(network, prefix_len);
+
+        old
+    }
+
+    fn install_prefix(
+        &mut self,
+        network: A::Bits,
+        prefix_len: u8,
+        dest: V,
+        meta: Option<RouteMeta>,
+    ) -> Option<V> {
+        let node = self.insert_node(network, prefix_len);
+
+        if !self.node(node).is_terminal {
+            self.count_route(prefix_len);
+        }
+        self.generation += 1;
+
+        let node = self.node_mut(node);
+        node.is_terminal = true;
+        node.meta = meta;
+        node.dest.replace(dest)
+    }
+
+    /// Returns the node for exactly this prefix, adding it to the trie if
+    /// needed.
+    fn insert_node(&mut self, network: A::Bits, prefix_len: u8) -> u32 {
+        let mut curr_node = ROOT;
+
+        loop {
+            let node = self.node(curr_node);
+            if node.prefix_len == prefix_len {
+                break curr_node;
+            }
+
+            let bit_idx = bit_at(network, node.prefix_len);
+
+            let Some(next) = node.edges[bit_idx] else {
+                let leaf = self.alloc_node(network, prefix_len);
+                self.node_mut(curr_node).edges[bit_idx] = Some(leaf);
+                break leaf;
+            };
+
+            let next_node = self.node(next);
+            if next_node.matches(network, prefix_len) {
+                curr_node = next;
+                continue;
+            }
+
+            // `next` skips past the point where it diverges from the new
+            // prefix, so a node is spliced in at the divergence.
+            let common = ((network ^ next_node.network).leading_zeros() as u8)
+                .min(prefix_len)
+                .min(next_node.prefix_len);
+            let next_bit_idx = bit_at(next_node.network, common);
+
+            let split = self.alloc_node(network & mask(common), common);
+            self.node_mut(split).edges[next_bit_idx] = Some(next);
+            self.node_mut(curr_node).edges[bit_idx] = Some(split);
+
+            if common == prefix_len {
+                break split;
+            }
+
+            let leaf = self.alloc_node(network, prefix_len);
+            self.node_mut(split).edges[bit_at(network, common)] = Some(leaf);
+            break leaf;
+        }
+    }
+
+    /// Builds a table from `(start, end, dest)` routes, stopping at the first
+    /// one that fails to insert.
+    ///
+    /// `Table` can't implement `FromIterator` since inserting is fallible, so
+    /// this stands in for `collect::<Result<Table, _>>()`.
+    pub fn try_from_iter<S, E, I>(routes: I) -> Result<Self, RotabError>
+    where
+        S: IntoAddr<A>,
+        E: IntoAddr<A>,
+        I: IntoIterator<Item = (S, E, V)>,
+        V: Clone,
+    {
+        let mut table = Self::new();
+        table.try_extend(routes)?;
+
+        Ok(table)
+    }
+
+    /// Inserts every `(start, end, dest)` route, stopping at the first one
+    /// that fails. Routes before it stay inserted.
+    pub fn try_extend<S, E, I>(&mut self, routes: I) -> Result<(), RotabError>
+    where
+        S: IntoAddr<A>,
+        E: IntoAddr<A>,
+        I: IntoIterator<Item = (S, E, V)>,
+        V: Clone,
+    {
+        for (start, end, dest) in routes {
+            self.insert_range(start, end, dest)?;
+        }
+
+        Ok(())
+    }
+
+    /// Inserts every `(start, end, dest)` route that is valid, rather than
+    /// stopping at the first that isn't like `try_extend`. Returns the
+    /// 0-based index and error of each route that failed.
+    pub fn insert_many<S, E, I>(&mut self, routes: I) -> Vec<(usize, RotabError)>
+    where
+        S: IntoAddr<A>,
+        E: IntoAddr<A>,
+        I: IntoIterator<Item = (S, E, V)>,
+        V: Clone,
+    {
+        routes
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, (start, end, dest))| {
+                self.insert_range(start, end, dest).err().map(|e| (i, e))
+            })
+            .collect()
+    }
+
+    /// Like `try_extend`, but calls `progress` with the number of routes
+    /// inserted so far after every `every` routes, so a long load can drive
+    /// a progress bar or yield to an executor. An `every` of 0 never calls
+    /// it. Returns the number of routes inserted.
+    pub fn load_from_iter_with_progress<S, E, I>(
+        &mut self,
+        routes: I,
+        every: usize,
+        mut progress: impl FnMut(usize),
+    ) -> Result<usize, RotabError>
+    where
+        S: IntoAddr<A>,
+        E: IntoAddr<A>,
+        I: IntoIterator<Item = (S, E, V)>,
+        V: Clone,
+    {
+        let mut loaded = 0_usize;
+        for (start, end, dest) in routes {
+            self.insert_range(start, end, dest)?;
+
+            loaded += 1;
+            if loaded.is_multiple_of(every) {
+                progress(loaded);
+            }
+        }
+
+        Ok(loaded)
+    }
+
+    /// Inserts every route of `other` with its metadata, resolving prefixes
+    /// installed in both tables according to `policy`. Under `Overwrite`, a
+    /// route installed with a lower admin distance is kept, as
+    /// `insert_range_with_meta` would.
+    pub fn merge(&mut self, other: &Table<V, A>, policy: MergePolicy) -> Result<(), RotabError>
+    where
+        V: Clone,
+    {
+        let is_installed =
+            |table: &Self, network, prefix_len| table.inserted_route(network, prefix_len).is_some();
+        let routes = other.inserted_routes();
+
+        if policy == MergePolicy::Error {
+            let conflicts = routes
+                .iter()
+                .filter(|&&(network, prefix_len, _)| is_installed(self, network, prefix_len))
+                .map(|&(network, prefix_len, _)| (A::from_bits(network).into(), prefix_len))
+                .collect::<Vec<_>>();
+            if !conflicts.is_empty() {
+                return Err(RotabError::MergeConflict(conflicts));
+            }
+        }
+
+        for (network, prefix_len, node) in routes {
+            if policy == MergePolicy::KeepExisting && is_installed(self, network, prefix_len) {
+                continue;
+            }
+
+            let Some(dest) = &node.dest else {
+                continue;
+            };
+            let meta = node.meta;
+            if let Some(meta) = meta
+                && self.preferred_distance(network, prefix_len, meta).is_some()
+            {
+                continue;
+            }
+            self.insert_prefix_with_meta(network, prefix_len, dest.clone(), meta);
+        }
+
+        Ok(())
+    }
+
+    /// Inserts the routes listed in `input`, one `CIDR dest` pair per line,
+    /// separated by whitespace or a comma. An arrow between the two is
+    /// allowed, so the output of `Display` loads back. Blank lines and lines
+    /// starting with `#` are skipped.
+    ///
+    /// Returns the number of routes loaded. Loading stops at the first bad
+    /// line, keeping the routes before it.
+    pub fn load_from_str(&mut self, input: &str) -> Result<usize, LoadError>
+    where
+        V: FromStr,
+    {
+        let mut loaded = 0;
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let err = |error| LoadError { line: i + 1, error };
+            let invalid = || err(RotabError::InvalidRoute(line.to_owned()));
+
+            let mut fields = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|field| !field.is_empty() && *field != "->");
+            let (Some(cidr), Some(dest), None) = (fields.next(), fields.next(), fields.next())
+            else {
+                return Err(invalid());
+            };
+
+            let dest = dest.parse().map_err(|_| invalid())?;
+            self.insert_cidr(cidr, dest).map_err(err)?;
+            loaded += 1;
+        }
+
+        Ok(loaded)
+    }
+
+    /// Compares the routes of this table against `other`.
+    pub fn diff(&self, other: &Table<V, A>) -> TableDiff<A>
+    where
+        V: PartialEq,
+    {
+        let ours = self
+            .routes()
+            .map(|(network, prefix_len, dest)| ((network, prefix_len), dest))
+            .collect::<BTreeMap<_, _>>();
+        let theirs = other
+            .routes()
+            .map(|(network, prefix_len, dest)| ((network, prefix_len), dest))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut diff = TableDiff {
+            added: vec![],
+            removed: vec![],
+            changed: vec![],
+        };
+        for (&prefix, dest) in &ours {
+            match theirs.get(&prefix) {
+                Some(other_dest) if other_dest != dest => diff.changed.push(prefix),
+                Some(_) => {}
+                None => diff.removed.push(prefix),
+            }
+        }
+        diff.added = theirs
+            .into_keys()
+            .filter(|prefix| !ours.contains_key(prefix))
+            .collect();
+
+        diff
+    }
+
+    /// Returns the ranges where a lookup in `other` gives a different result
+    /// than in this table, as prefixes in address order. Unlike `diff`,
+    /// this compares the effective longest matches, so a route in one table
+    /// that only repeats what a covering route already does is not reported.
+    pub fn coverage_diff(&self, other: &Table<V, A>) -> Vec<((A, u8), CoverageState<V>)>
+    where
+        V: Clone + PartialEq,
+    {
+        // Lookups in both tables are fixed across each part of a prefix not
+        // under a longer prefix of either table, so one address per part is
+        // enough to compare them.
+        let mut union = Table::<(), A>::new();
+        for (network, prefix_len, _) in self.routes().chain(other.routes()) {
+            union.insert_prefix(network.to_bits(), prefix_len, ());
+        }
+
+        let mut parts = vec![];
+        for (_, node) in union.nodes_from(ROOT) {
+            if !node.is_terminal {
+                continue;
+            }
+
+            let last = node.network | !mask::<A::Bits>(node.prefix_len);
+            let mut next = Some(node.network);
+            for child in node.children().map(|next| union.node(next)) {
+                for (covered_first, covered_last) in
+                    union.outermost_routes_within(child.network, child.prefix_len)
+                {
+                    if let Some(curr) = next
+                        && curr < covered_first
+                    {
+                        parts.push((curr, covered_first - A::Bits::ONE));
+                    }
+                    next = (covered_last != last).then(|| covered_last + A::Bits::ONE);
+                }
+            }
+            if let Some(curr) = next {
+                parts.push((curr, last));
+            }
+        }
+
+        let mut diffs = vec![];
+        for (first, last) in parts {
+            let ours = self
+                .select_match(first, A::BITS as u8)
+                .and_then(|node| node.dest.clone());
+            let theirs = other
+                .select_match(first, A::BITS as u8)
+                .and_then(|node| node.dest.clone());
+            let state = match (ours, theirs) {
+                (Some(this), Some(other)) if this != other => {
+                    CoverageState::Differs { this, other }
+                }
+                (Some(this), None) => CoverageState::OnlyThis(this),
+                (None, Some(other)) => CoverageState::OnlyOther(other),
+                _ => continue,
+            };
+
+            for (first, last) in Self::range_to_blocks(first, last) {
+                let (network, prefix_len) = Self::prefix(first, last);
+                diffs.push(((A::from_bits(network), prefix_len), state.clone()));
+            }
+        }
+        diffs.sort_by_key(|&(prefix, _)| prefix);
+
+        diffs
+    }
+
+    /// Inserts a route for a prefix in CIDR notation, e.g. `10.0.0.0/24`.
+    /// Host bits set in the address are masked off. Returns the previous
+    /// destination of the prefix, if any.
+    pub fn insert_cidr(&mut self, cidr: &str, dest: V) -> Result<Option<V>, RotabError> {
+        let (network, prefix_len) = Self::parse_cidr(cidr)?;
+
+        Ok(self.insert_prefix(network, prefix_len, dest))
+    }
+
+    /// Like `insert_cidr`, but fails with `InvalidCidr` if the address has
+    /// host bits set, e.g. `10.0.0.5/24`.
+    pub fn insert_cidr_strict(&mut self, cidr: &str, dest: V) -> Result<Option<V>, RotabError> {
+        let (network, prefix_len) = Self::parse_cidr_strict(cidr)?;
+
+        Ok(self.insert_prefix(network, prefix_len, dest))
+    }
+
+    /// Inserts a route for `start..=end` as one prefix, for callers that
+    /// expect their ranges to be aligned. Unlike `insert_range`, fails with
+    /// `NotSingleCidr` if the range isn't exactly one CIDR block.
+    pub fn insert_cidr_range_strict(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<Option<V>, RotabError> {
+        let (network, prefix_len) = Self::parse_block(start, end)?;
+
+        Ok(self.insert_prefix(network, prefix_len, dest))
+    }
+
+    /// Inserts routes for `start..=end` only if no address in the range is
+    /// covered already, for strict configs that mustn't nest prefixes.
+    /// Fails with `Overlaps`, listing every installed route that covers or
+    /// lies inside the range, and leaves the table unchanged.
+    pub fn try_insert_disjoint(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<(), RotabError>
+    where
+        V: Clone,
+    {
+        let (start, end) = Self::parse_range(start, end)?;
+        let blocks = Self::range_to_blocks(start, end)
+            .into_iter()
+            .map(|(first, last)| Self::prefix(first, last))
+            .collect::<Vec<_>>();
+
+        let mut conflicting = vec![];
+        for &(network, prefix_len) in &blocks {
+            conflicting.extend(
+                self.matching_nodes(network, prefix_len)
+                    .filter(|node| node.prefix_len < prefix_len)
+                    .map(|node| (node.network, node.prefix_len)),
+            );
+            conflicting.extend(
+                self.outermost_routes_within(network, prefix_len)
+                    .into_iter()
+                    .map(|(first, last)| Self::prefix(first, last)),
+            );
+        }
+        if !conflicting.is_empty() {
+            // A route covering several blocks is found once for each.
+            conflicting.sort_unstable();
+            conflicting.dedup();
+            return Err(RotabError::Overlaps {
+                conflicting: conflicting
+                    .into_iter()
+                    .map(|(network, prefix_len)| (A::from_bits(network).into(), prefix_len))
+                    .collect(),
+            });
+        }
+
+        for (network, prefix_len) in blocks {
+            self.insert_prefix(network, prefix_len, dest.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the route for the single prefix `start..=end` with routes for
+    /// each of its sub-prefixes of length `new_len`, keeping its destination
+    /// and metadata. Sub-prefixes that already have a route keep it.
+    ///
+    /// Returns the sub-prefixes inserted, in address order, or nothing if
+    /// the prefix had no route. Fails with `InvalidPrefixLen` unless
+    /// `new_len` is longer than the prefix.
+    pub fn split_prefix(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        new_len: u8,
+    ) -> Result<Vec<(A, u8)>, RotabError>
+    where
+        V: Clone,
+    {
+        let (network, prefix_len) = Self::parse_block(start, end)?;
+        if new_len <= prefix_len || new_len as u32 > A::BITS {
+            return Err(RotabError::InvalidPrefixLen(new_len));
+        }
+
+        self.unmerge_all();
+
+        let mut created = vec![];
+        if let Some(meta) = self.find_route(network, prefix_len).map(|node| node.meta)
+            && let Some(dest) = self.remove_prefix(network, prefix_len)
+        {
+            let last = network | !mask::<A::Bits>(prefix_len);
+            let step = A::Bits::ONE << (A::BITS - new_len as u32);
+
+            let mut curr = network;
+            loop {
+                if self.find_route(curr, new_len).is_none() {
+                    self.install_prefix(curr, new_len, dest.clone(), meta);
+                    created.push((A::from_bits(curr), new_len));
+                }
+
+                if curr | !mask::<A::Bits>(new_len) == last {
+                    break;
+                }
+                curr = curr + step;
+            }
+        }
+
+        self.merge_all();
+        Ok(created)
+    }
+
+    /// Removes the route for a prefix in CIDR notation, masking host bits
+    /// like `insert_cidr`. Returns whether the route existed.
+    pub fn remove_cidr(&mut self, cidr: &str) -> Result<bool, RotabError> {
+        let (network, prefix_len) = Self::parse_cidr(cidr)?;
+
+        Ok(self.remove_route(network, prefix_len).is_some())
+    }
+
+    /// Removes the routes previously inserted for `start..=end`, pruning any
+    /// interior nodes left without children. Returns whether any route existed.
+    pub fn remove_range(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<bool, RotabError> {
+        let (start, end) = Self::parse_range(start, end)?;
+
+        let mut removed = false;
+        for (first, last) in Self::range_to_blocks(start, end) {
+            let (network, prefix_len) = Self::prefix(first, last);
+            removed |= self.remove_route(network, prefix_len).is_some();
+        }
+
+        Ok(removed)
+    }
+
+    /// Punches a hole for a prefix in CIDR notation, so that no address in
+    /// it matches any route afterwards. Routes inside the prefix are
+    /// removed, and routes covering it are split into the sub-prefixes
+    /// around it, keeping their destination and metadata. Those sub-prefixes
+    /// don't replace more specific routes already installed for them.
+    /// Returns whether any route was removed or split.
+    pub fn subtract(&mut self, cidr: &str) -> Result<bool, RotabError>
+    where
+        V: Clone,
+    {
+        let (hole, hole_len) = Self::parse_cidr(cidr)?;
+        self.unmerge_all();
+
+        let covering = self
+            .matching_nodes(hole, hole_len)
+            .filter(|node| node.prefix_len < hole_len)
+            .filter_map(|node| Some((node.network, node.prefix_len, node.dest.clone()?, node.meta)))
+            .collect::<Vec<_>>();
+
+        let inside = self
+            .nodes_within(hole, hole_len)
+            .filter(|node| node.is_terminal)
+            .map(|node| (node.network, node.prefix_len))
+            .collect::<Vec<_>>();
+
+        for &(network, prefix_len) in &inside {
+            self.remove_prefix(network, prefix_len);
+        }
+
+        // Split the longest covering route first, so the sub-prefixes it
+        // leaves take precedence over those of the shorter ones around it.
+        for (network, prefix_len, dest, meta) in covering.iter().rev() {
+            self.remove_prefix(*network, *prefix_len);
+
+            for len in *prefix_len + 1..=hole_len {
+                let sibling = (hole & mask(len)) ^ A::Bits::ONE << (A::BITS - len as u32);
+                if self.find_route(sibling, len).is_none() {
+                    self.install_prefix(sibling, len, dest.clone(), *meta);
+                }
+            }
+        }
+
+        self.merge_all();
+        Ok(!covering.is_empty() || !inside.is_empty())
+    }
+
+    /// Removes the route for the prefix, also when `new_auto_aggregate`
+    /// merged it into a covering route, and merges what's left again.
+    fn remove_route(&mut self, network: A::Bits, prefix_len: u8) -> Option<V> {
+        self.unmerge(network, prefix_len);
+        let dest = self.remove_prefix(network, prefix_len);
+        self.merge_at(network, prefix_len);
+
+        dest
+    }
+
+    fn remove_prefix(&mut self, network: A::Bits, prefix_len: u8) -> Option<V> {
+        let mut path = vec![];
+        let mut curr_node = ROOT;
+
+        loop {
+            let node = self.node(curr_node);
+            if node.prefix_len == prefix_len {
+                break;
+            }
+
+            let bit_idx = bit_at(network, node.prefix_len);
+            match node.edges[bit_idx] {
+                Some(next) if self.node(next).matches(network, prefix_len) => {
+                    path.push((curr_node, bit_idx));
+                    curr_node = next;
+                }
+                _ => return None,
+            }
+        }
+
+        let node = self.node_mut(curr_node);
+        let dest = node.dest.take();
+        node.meta = None;
+        node.is_terminal = false;
+        node.hits = HitCounter::default();
+
+        if dest.is_some() {
+            self.uncount_route(prefix_len);
+            self.generation += 1;
+        }
+
+        // Drop the node if it no longer holds a route or a branch, and then
+        // its parent if that was only kept around to branch.
+        while let Some((parent, bit_idx)) = path.pop() {
+            let node = self.node(curr_node);
+            if node.is_terminal || node.children().count() == 2 {
+                break;
+            }
+
+            let child = node.children().next();
+            self.node_mut(parent).edges[bit_idx] = child;
+            self.free.push(curr_node);
+
+            if child.is_some() {
+                break;
+            }
+            curr_node = parent;
+        }
+
+        dest
+    }
+
+    /// Inserts a route for the single prefix `start..=end`, reporting whether
+    /// it was new, already installed with `dest`, or pointed elsewhere.
+    /// Reinserting an existing route is a no-op, so reloading the same
+    /// config only reports what changed. Fails with `NotSingleCidr` if the
+    /// range isn't a single prefix.
+    pub fn insert_with_outcome(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<InsertOutcome<V>, RotabError>
+    where
+        V: PartialEq,
+    {
+        let (network, prefix_len) = Self::parse_block(start, end)?;
+
+        if self
+            .inserted_route(network, prefix_len)
+            .is_some_and(|node| node.dest.as_ref() == Some(&dest))
+        {
+            return Ok(InsertOutcome::Unchanged);
+        }
+
+        Ok(match self.insert_prefix(network, prefix_len, dest) {
+            Some(old) => InsertOutcome::Updated(old),
+            None => InsertOutcome::Created,
+        })
+    }
+
+    /// Returns the entry for the prefix `start..=end`, for in-place
+    /// insertion or update. Fails with `NotSingleCidr` if the range isn't a
+    /// single prefix.
+    pub fn entry(
+        &mut self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<Entry<'_, V, A>, RotabError> {
+        let (network, prefix_len) = Self::parse_block(start, end)?;
+        self.unmerge(network, prefix_len);
+
+        Ok(Entry::new(self, network, prefix_len))
+    }
+
+    /// Returns whether exactly the prefix `start..=end` is installed, as
+    /// opposed to `lookup`, which would also match a covering route.
+    pub fn contains_exact(
+        &self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<bool, RotabError> {
+        let (network, prefix_len) = Self::parse_block(start, end)?;
+
+        Ok(self.inserted_route(network, prefix_len).is_some())
+    }
+
+    /// Returns the destination of exactly the prefix `network/prefix_len`,
+    /// or `None` if it has no route of its own, even if a shorter one
+    /// covers it. Host bits of `network` are masked off.
+    pub fn get_exact(
+        &self,
+        network: impl IntoAddr<A>,
+        prefix_len: u8,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        let network = network.into_addr()?.to_bits();
+        if prefix_len as u32 > A::BITS {
+            return Err(RotabError::InvalidPrefixLen(prefix_len));
+        }
+
+        Ok(self
+            .inserted_route(network & mask(prefix_len), prefix_len)
+            .and_then(|node| node.dest.clone()))
+    }
+
+    /// Returns whether installed routes cover all of `start..=end`, each part
+    /// by a route of the same or a shorter prefix. Unlike `contains_exact`,
+    /// a covering `10.0.0.0/16` counts for `10.0.1.0/24`.
+    pub fn covers(
+        &self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<bool, RotabError> {
+        let (start, end) = Self::parse_range(start, end)?;
+
+        Ok(Self::range_to_blocks(start, end)
+            .into_iter()
+            .all(|(first, last)| {
+                let (network, prefix_len) = Self::prefix(first, last);
+                self.is_covered(network, prefix_len)
+            }))
+    }
+
+    /// Returns every route inside `start..=end`, in prefix order. Only the
+    /// subtrees under the range are visited, rather than the whole table.
+    /// Routes covering the range from outside it, like a default route, are
+    /// not included.
+    pub fn routes_within(
+        &self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<Vec<Match<V, A>>, RotabError>
+    where
+        V: Clone,
+    {
+        let (start, end) = Self::parse_range(start, end)?;
+
+        let mut routes = vec![];
+        for (first, last) in Self::range_to_blocks(start, end) {
+            let (network, prefix_len) = Self::prefix(first, last);
+
+            for node in self.nodes_within(network, prefix_len) {
+                if node.is_terminal
+                    && let Some(dest) = node.dest.clone()
+                {
+                    routes.push(Match {
+                        dest,
+                        prefix_len: node.prefix_len,
+                        network: A::from_bits(node.network),
+                    });
+                }
+            }
+        }
+
+        Ok(routes)
+    }
+
+    fn is_covered(&self, network: A::Bits, prefix_len: u8) -> bool {
+        let mut curr_node = ROOT;
+
+        loop {
+            let node = self.node(curr_node);
+            if !node.matches(network, prefix_len) {
+                return false;
+            }
+            if node.is_terminal {
+                return true;
+            }
+            if node.prefix_len == prefix_len {
+                return false;
+            }
+
+            match node.edges[bit_at(network, node.prefix_len)] {
+                Some(next) => curr_node = next,
+                None => return false,
+            }
+        }
+    }
+
+    /// Returns the terminal node for exactly this prefix.
+    fn find_route(&self, network: A::Bits, prefix_len: u8) -> Option<&Node<V, A::Bits>> {
+        self.find_node(network, prefix_len)
+            .map(|idx| self.node(idx))
+            .filter(|node| node.is_terminal)
+    }
+
+    fn find_node(&self, network: A::Bits, prefix_len: u8) -> Option<u32> {
+        let mut curr_node = ROOT;
+
+        loop {
+            let node = self.node(curr_node);
+            if node.prefix_len == prefix_len {
+                return Some(curr_node);
+            }
+
+            match node.edges[bit_at(network, node.prefix_len)] {
+                Some(next) if self.node(next).matches(network, prefix_len) => curr_node = next,
+                _ => return None,
+            }
+        }
+    }
+
+    pub fn lookup(&self, ip: impl IntoAddr<A>) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        Ok(self
+            .lookup_detailed(ip)?
+            .map(|m| m.dest)
+            .or_else(|| self.fallback.clone()))
+    }
+
+    /// Like `lookup`, but fails with `NoRoute` if no route covers `ip`, for
+    /// callers that treat a miss as an error.
+    pub fn lookup_required(&self, ip: impl IntoAddr<A>) -> Result<V, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?;
+
+        self.lookup(ip)?
+            .ok_or(RotabError::NoRoute { ip: ip.into() })
+    }
+
+    /// Like `lookup`, but records the walk through the trie, for debugging
+    /// unexpected matches. Doesn't count as a hit for `hit_counts`.
+    pub fn lookup_trace(&self, ip: impl IntoAddr<A>) -> Result<LookupTrace<V>, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?.to_bits();
+
+        let mut depth = 0;
+        let mut terminals_seen = vec![];
+        let mut curr_node = Some(ROOT);
+        while let Some(idx) = curr_node.take() {
+            let node = self.node(idx);
+            if !node.matches(ip, A::BITS as u8) {
+                break;
+            }
+
+            depth = node.prefix_len;
+            if node.is_terminal
+                && let Some(dest) = node.dest.clone()
+            {
+                terminals_seen.push((node.prefix_len, dest));
+            }
+            if (node.prefix_len as u32) < A::BITS {
+                curr_node = node.edges[bit_at(ip, node.prefix_len)];
+            }
+        }
+
+        Ok(LookupTrace {
+            bits_consumed: (0..depth).map(|i| bit_at(ip, i) as u8).collect(),
+            terminals_seen,
+            result: self
+                .select_match(ip, A::BITS as u8)
+                .and_then(|node| node.dest.clone())
+                .or_else(|| self.fallback.clone()),
+        })
+    }
+
+    /// Returns whether any route covers `ip`, or the table has a fallback
+    /// for it. Stops at the first covering route instead of looking for the
+    /// longest.
+    pub fn contains_ip(&self, ip: impl IntoAddr<A>) -> Result<bool, RotabError> {
+        let ip = ip.into_addr()?.to_bits();
+
+        Ok(self.fallback.is_some() || self.matching_nodes(ip, A::BITS as u8).next().is_some())
+    }
+
+    /// Looks up each address in turn, yielding the results in input order.
+    pub fn lookup_many<I>(
+        &self,
+        ips: impl IntoIterator<Item = I>,
+    ) -> impl Iterator<Item = Result<Option<V>, RotabError>>
+    where
+        I: IntoAddr<A>,
+        V: Clone,
+    {
+        ips.into_iter().map(|ip| self.lookup(ip))
+    }
+
+    /// Like `lookup`, but only considers prefixes up to `max_len` bits long,
+    /// which bounds how far the walk descends.
+    pub fn lookup_bounded(&self, ip: impl IntoAddr<A>, max_len: u8) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        Ok(self
+            .lookup_excluding_longer_than(ip, max_len)?
+            .map(|m| m.dest)
+            .or_else(|| self.fallback.clone()))
+    }
+
+    /// Like `lookup_bounded`, but also reports which prefix matched. Passing
+    /// one less than a route's length previews what its addresses would
+    /// match if it were withdrawn. Neither counts as a hit for
+    /// `hit_counts`.
+    pub fn lookup_excluding_longer_than(
+        &self,
+        ip: impl IntoAddr<A>,
+        max_len: u8,
+    ) -> Result<Option<Match<V, A>>, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?.to_bits();
+
+        Ok(self.select_route(ip, max_len).and_then(Self::to_match))
+    }
+
+    /// Like `lookup`, but also reports which prefix matched.
+    pub fn lookup_detailed(&self, ip: impl IntoAddr<A>) -> Result<Option<Match<V, A>>, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?.to_bits();
+
+        Ok(self.longest_route(ip).and_then(Self::to_match))
+    }
+
+    fn to_match((network, prefix_len, node): RouteRef<'_, V, A::Bits>) -> Option<Match<V, A>>
+    where
+        V: Clone,
+    {
+        node.dest.clone().map(|dest| Match {
+            dest,
+            prefix_len,
+            network: A::from_bits(network),
+        })
+    }
+
+    /// Returns the match for `ip` together with a backup: the route that
+    /// would take over if the match were withdrawn, picked among the other
+    /// routes covering `ip` the same way, preferred routes first. Either is
+    /// `None` if no such route exists.
+    #[allow(clippy::type_complexity)]
+    pub fn lookup_with_backup(
+        &self,
+        ip: impl IntoAddr<A>,
+    ) -> Result<(Option<Match<V, A>>, Option<Match<V, A>>), RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?.to_bits();
+
+        let Some(primary) = self.longest_route(ip) else {
+            return Ok((None, None));
+        };
+        let backup = select_preferred(
+            self.covering_routes(ip, A::BITS as u8)
+                .into_iter()
+                .filter(|&(_, prefix_len, _)| prefix_len != primary.1),
+            |route| route.2.meta,
+        );
+
+        Ok((Self::to_match(primary), backup.and_then(Self::to_match)))
+    }
+
+    /// Like `lookup`, but formats the destination with `Display`, for FFI and
+    /// scripting bridges that pass strings around.
+    pub fn lookup_str(&self, ip: &str) -> Result<Option<String>, RotabError>
+    where
+        V: Clone + fmt::Display,
+    {
+        Ok(self.lookup(ip)?.map(|dest| dest.to_string()))
+    }
+
+    /// Like `lookup`, but also returns the matched prefix in CIDR notation,
+    /// e.g. `("10.0.1.0/24", dest)`.
+    pub fn match_cidr(&self, ip: impl IntoAddr<A>) -> Result<Option<(String, V)>, RotabError>
+    where
+        V: Clone,
+    {
+        Ok(self
+            .lookup_detailed(ip)?
+            .map(|m| (format!("{}/{}", m.network, m.prefix_len), m.dest)))
+    }
+
+    /// Like `lookup`, but also returns the metadata stored with the matched
+    /// route, if it was inserted with any.
+    pub fn lookup_with_meta(
+        &self,
+        ip: impl IntoAddr<A>,
+    ) -> Result<Option<(V, Option<RouteMeta>)>, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?.to_bits();
+
+        Ok(self
+            .longest_match(ip)
+            .and_then(|node| node.dest.clone().map(|dest| (dest, node.meta)))
+            .or_else(|| self.fallback.clone().map(|dest| (dest, None))))
+    }
+
+    /// Returns every prefix covering `ip`, from least to most specific.
+    pub fn lookup_all(&self, ip: impl IntoAddr<A>) -> Result<Vec<Match<V, A>>, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?.to_bits();
+
+        Ok(self
+            .covering_routes(ip, A::BITS as u8)
+            .into_iter()
+            .filter_map(Self::to_match)
+            .collect())
+    }
+
+    /// Like `select_match` over every prefix length, but counts the hit for
+    /// `hit_counts` if the table was made with `new_counted`. Only lookups
+    /// that forward `ip` go through here; bounded, tracing and listing
+    /// lookups call `select_match` or `matching_nodes` directly.
+    fn longest_match(&self, ip: A::Bits) -> Option<&Node<V, A::Bits>> {
+        let node = self.select_match(ip, A::BITS as u8);
+        if self.counted
+            && let Some(node) = node
+        {
+            node.hits.record();
+        }
+
+        node
+    }
+
+    /// Returns the longest route covering `ip` up to `max_len`, unless a
+    /// route marked `preferred` also covers it, in which case the longest
+    /// preferred route wins.
+    fn select_match(&self, ip: A::Bits, max_len: u8) -> Option<&Node<V, A::Bits>> {
+        select_preferred(self.matching_nodes(ip, max_len), |node| node.meta)
+    }
+
+    /// Like `select_match`, but reports the prefix a table without merging
+    /// would have matched, which in a table made with `new_auto_aggregate`
+    /// may be hidden under the node's.
+    fn select_route(&self, ip: A::Bits, max_len: u8) -> Option<RouteRef<'_, V, A::Bits>> {
+        if self.auto_aggregate.is_none() {
+            return self
+                .select_match(ip, max_len)
+                .map(|node| (node.network, node.prefix_len, node));
+        }
+
+        select_preferred(self.covering_routes(ip, max_len), |route| route.2.meta)
+    }
+
+    /// Like `longest_match`, but with the prefix from `select_route`.
+    fn longest_route(&self, ip: A::Bits) -> Option<RouteRef<'_, V, A::Bits>> {
+        let route = self.select_route(ip, A::BITS as u8);
+        if self.counted
+            && let Some((_, _, node)) = route
+        {
+            node.hits.record();
+        }
+
+        route
+    }
+
+    /// Every route covering `ip` up to `max_len`, from least to most
+    /// specific, as a table without merging would have them.
+    fn covering_routes(&self, ip: A::Bits, max_len: u8) -> Vec<RouteRef<'_, V, A::Bits>> {
+        if self.auto_aggregate.is_some() {
+            return self.inserted_matches(ip, max_len);
+        }
+
+        self.matching_nodes(ip, max_len)
+            .map(|node| (node.network, node.prefix_len, node))
+            .collect()
+    }
+
+    /// Walks from the root towards `ip`, yielding each terminal node whose
+    /// prefix covers it, up to a prefix length of `max_len`.
+    fn matching_nodes(&self, ip: A::Bits, max_len: u8) -> impl Iterator<Item = &Node<V, A::Bits>> {
+        let mut curr_node = Some(ROOT);
+
+        core::iter::from_fn(move || {
+            while let Some(idx) = curr_node.take() {
+                let node = self.node(idx);
+                if node.prefix_len > max_len || !node.matches(ip, A::BITS as u8) {
+                    break;
+                }
+
+                if (node.prefix_len as u32) < A::BITS {
+                    curr_node = node.edges[bit_at(ip, node.prefix_len)];
+                }
+
+                if node.is_terminal {
+                    return Some(node);
+                }
+            }
+
+            None
+        })
+    }
+
+    fn parse_cidr(cidr: &str) -> Result<(A::Bits, u8), RotabError> {
+        let (addr, prefix_len) = Self::parse_cidr_unmasked(cidr)?;
+
+        Ok((addr & mask(prefix_len), prefix_len))
+    }
+
+    /// Like `parse_cidr`, but rejects an address with host bits set.
+    fn parse_cidr_strict(cidr: &str) -> Result<(A::Bits, u8), RotabError> {
+        let (addr, prefix_len) = Self::parse_cidr_unmasked(cidr)?;
+        if addr & mask(prefix_len) != addr {
+            return Err(RotabError::InvalidCidr(cidr.to_owned()));
+        }
+
+        Ok((addr, prefix_len))
+    }
+
+    fn parse_cidr_unmasked(cidr: &str) -> Result<(A::Bits, u8), RotabError> {
+        let invalid = || RotabError::InvalidCidr(cidr.to_owned());
+
+        let (addr, prefix_len) = cidr.split_once('/').ok_or_else(invalid)?;
+
+        let addr = A::from_str(addr)?.to_bits();
+        let prefix_len = prefix_len.parse::<u8>().map_err(|_| invalid())?;
+        if prefix_len as u32 > A::BITS {
+            return Err(RotabError::InvalidPrefixLen(prefix_len));
+        }
+
+        Ok((addr, prefix_len))
+    }
+
+    /// Merges pairs of sibling routes with the same destination and metadata
+    /// into their parent prefix, e.g. `10.0.0.0/25` and `10.0.0.128/25` into
+    /// `10.0.0.0/24`, repeating up the trie. A pair whose parent already
+    /// has a route is only merged if that route has the same destination and
+    /// metadata, in which case the halves are just removed. A table made
+    /// with `new_auto_aggregate` remembers the merged routes, as it does for
+    /// its own merges. Returns the number of merges.
+    pub fn aggregate(&mut self) -> usize
+    where
+        V: PartialEq,
+    {
+        self.aggregate_with(V::eq)
+    }
+
+    fn aggregate_with(&mut self, eq: fn(&V, &V) -> bool) -> usize {
+        let mut by_len = vec![vec![]; A::BITS as usize + 1];
+        for (network, prefix_len, _) in self.routes() {
+            by_len[prefix_len as usize].push(network.to_bits());
+        }
+
+        let mut merged = 0;
+        for prefix_len in (1..=A::BITS as u8).rev() {
+            let sibling_bit = A::Bits::ONE << (A::BITS - prefix_len as u32);
+
+            for network in core::mem::take(&mut by_len[prefix_len as usize]) {
+                // Each pair is handled once, from its lower half.
+                if network & sibling_bit != A::Bits::ZERO {
+                    continue;
+                }
+
+                let parent_installed = self.find_route(network, prefix_len - 1).is_some();
+                if !self.merge_pair(network, prefix_len, eq) {
+                    continue;
+                }
+
+                if !parent_installed {
+                    by_len[prefix_len as usize - 1].push(network);
+                }
+                merged += 1;
+            }
+        }
+
+        merged
+    }
+
+    /// Removes routes that repeat the destination and metadata of the
+    /// closest route covering them, e.g. a `10.1.0.0/16` under a
+    /// `10.0.0.0/8` with the same next-hop. Lookups give the same results
+    /// afterwards. Unlike `aggregate`, this works on nested prefixes rather
+    /// than siblings. Returns the number of routes removed.
+    pub fn prune_redundant(&mut self) -> usize
+    where
+        V: PartialEq,
+    {
+        self.unmerge_all();
+        let mut redundant = vec![];
+
+        // The routes covering the current node, the closest last.
+        let mut covering: Vec<&Node<V, A::Bits>> = vec![];
+        for (_, node) in self.nodes_from(ROOT) {
+            while covering
+                .last()
+                .is_some_and(|parent| !parent.matches(node.network, node.prefix_len))
+            {
+                covering.pop();
+            }
+
+            if node.is_terminal {
+                if covering
+                    .last()
+                    .is_some_and(|parent| parent.dest == node.dest && parent.meta == node.meta)
+                {
+                    redundant.push((node.network, node.prefix_len));
+                }
+                covering.push(node);
+            }
+        }
+
+        for &(network, prefix_len) in &redundant {
+            self.remove_prefix(network, prefix_len);
+        }
+
+        self.merge_all();
+        redundant.len()
+    }
+
+    /// Iterates over every route as `(network, prefix_len, dest)`, in
+    /// prefix order.
+    pub fn routes(&self) -> Routes<'_, V, A> {
+        Routes {
+            nodes: self.nodes_from(ROOT),
+            addr: PhantomData,
+        }
+    }
+
+    /// Renders the trie as a Graphviz DOT graph, for debugging. Each node is
+    /// labeled with its bit path (`*` for the root), routes are filled and
+    /// show their destination, and edges are labeled with the bit taken.
+    pub fn to_dot(&self) -> String
+    where
+        V: fmt::Display,
+    {
+        let mut dot = String::from("digraph rotab {\n    node [shape=box];\n");
+
+        for (idx, node) in self.nodes_from(ROOT) {
+            let mut label = (0..node.prefix_len)
+                .map(|i| {
+                    if bit_at(node.network, i) == 1 {
+                        '1'
+                    } else {
+                        '0'
+                    }
+                })
+                .collect::<String>();
+            if label.is_empty() {
+                label.push('*');
+            }
+
+            match node.dest.as_ref().filter(|_| node.is_terminal) {
+                Some(dest) => dot.push_str(&format!(
+                    "    n{idx} [label=\"{label}\\n{}/{} -> {dest}\", style=filled];\n",
+                    A::from_bits(node.network),
+                    node.prefix_len
+                )),
+                None => dot.push_str(&format!("    n{idx} [label=\"{label}\"];\n")),
+            }
+
+            for (bit, next) in node.edges.into_iter().enumerate() {
+                if let Some(next) = next {
+                    dot.push_str(&format!("    n{idx} -> n{next} [label=\"{bit}\"];\n"));
+                }
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Calls `f` with every route in the same order as `routes`, for
+    /// filtering or counting without collecting the routes first.
+    pub fn for_each_route<F: FnMut(A, u8, &V)>(&self, mut f: F) {
+        for (network, prefix_len, dest) in self.routes() {
+            f(network, prefix_len, dest);
+        }
+    }
+
+    /// Points every route whose destination is `old` at `new` instead, e.g.
+    /// to move traffic off a gateway that went down. Returns the number of
+    /// routes changed.
+    pub fn replace_next_hop(&mut self, old: V, new: V) -> usize
+    where
+        V: PartialEq + Clone,
+    {
+        let mut replaced = 0;
+        self.visit_routes_mut(|_, _, dest| {
+            if *dest == old {
+                *dest = new.clone();
+                replaced += 1;
+            }
+        });
+
+        if replaced > 0 {
+            self.generation += 1;
+        }
+        replaced
+    }
+
+    /// Calls `f` with every route in the same order as `routes`, letting it
+    /// change the destination in place. The generation always moves
+    /// forward, since there's no telling whether `f` changed anything.
+    pub fn for_each_mut<F: FnMut(A, u8, &mut V)>(&mut self, f: F) {
+        self.generation += 1;
+        self.visit_routes_mut(f);
+    }
+
+    /// `for_each_mut`, without touching the generation.
+    fn visit_routes_mut<F: FnMut(A, u8, &mut V)>(&mut self, mut f: F) {
+        self.unmerge_all();
+
+        let order = self
+            .nodes_from(ROOT)
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        for idx in order {
+            let node = self.node_mut(idx);
+            if node.is_terminal
+                && let Some(dest) = node.dest.as_mut()
+            {
+                f(A::from_bits(node.network), node.prefix_len, dest);
+            }
+        }
+
+        self.merge_all();
+    }
+}
+
+impl<V> Table<V, Ipv4Addr> {
+    /// Looks up an address given as an integer, as from `Ipv4Addr::to_bits`
+    /// or `u32::from_be_bytes` on a packet header, without parsing.
+    pub fn lookup_u32(&self, ip: u32) -> Option<&V> {
+        self.longest_match(ip)
+            .and_then(|node| node.dest.as_ref())
+            .or(self.fallback.as_ref())
+    }
+
+    /// Like `insert_range`, but parses both ends with `parse_lenient`, which
+    /// accepts zero-padded octets and surrounding whitespace.
+    pub fn insert_range_lenient(
+        &mut self,
+        start: &str,
+        end: &str,
+        dest: V,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        self.insert_range(parse_lenient(start)?, parse_lenient(end)?, dest)
+    }
+
+    /// Inserts a route for the prefix `addr/prefix_len`, with `addr` as an
+    /// integer. Host bits are masked off. Returns the previous destination of
+    /// the prefix, if any.
+    pub fn insert_u32_prefix(
+        &mut self,
+        addr: u32,
+        prefix_len: u8,
+        dest: V,
+    ) -> Result<Option<V>, RotabError> {
+        if prefix_len > 32 {
+            return Err(RotabError::InvalidPrefixLen(prefix_len));
+        }
+
+        Ok(self.insert_prefix(addr & mask::<u32>(prefix_len), prefix_len, dest))
+    }
+
+    /// Inserts a route for `addr` under a dotted netmask, as in legacy
+    /// `10.0.0.0 255.255.255.0` configs. Fails with `InvalidNetmask` unless
+    /// the mask's set bits are contiguous from the top. Host bits of `addr`
+    /// are masked off.
+    pub fn insert_masked(
+        &mut self,
+        addr: Ipv4Addr,
+        netmask: Ipv4Addr,
+        dest: V,
+    ) -> Result<Option<V>, RotabError> {
+        let prefix_len = netmask.to_bits().leading_ones() as u8;
+        if netmask.to_bits() != mask::<u32>(prefix_len) {
+            return Err(RotabError::InvalidNetmask(netmask.into()));
+        }
+
+        self.insert_u32_prefix(addr.to_bits(), prefix_len, dest)
+    }
+
+    /// Returns how many addresses the routes cover together. Nested prefixes
+    /// aren't counted twice: a route inside another adds nothing, so a /8
+    /// with a /24 under it covers 2^24 addresses.
+    pub fn covered_address_count(&self) -> u64 {
+        outermost(self.nodes_from(ROOT).map(|(_, node)| node))
+            .map(|node| 1_u64 << (32 - node.prefix_len))
+            .sum()
+    }
+
+    /// Splits `supernet` (in CIDR notation) into `buckets` equal segments and
+    /// returns the fraction of each segment covered by routes.
+    pub fn density(&self, supernet: &str, buckets: usize) -> Result<Vec<f64>, RotabError> {
+        let (network, prefix_len) = Self::parse_cidr(supernet)?;
+
+        let base = network as u64;
+        let size = 1_u64 << (32 - prefix_len);
+
+        let mut covered = vec![];
+        let mut curr_node = Some(ROOT);
+        while let Some(idx) = curr_node.take() {
+            let node = self.node(idx);
+
+            let common = node.prefix_len.min(prefix_len);
+            if (node.network ^ network) & mask::<u32>(common) != 0 {
+                break;
+            }
+
+            if node.prefix_len >= prefix_len {
+                self.covered_ranges(idx, &mut covered);
+            } else if node.is_terminal {
+                covered.push((base, base + size));
+            } else {
+                curr_node = node.edges[bit_at(network, node.prefix_len)];
+            }
+        }
+
+        // In u128, as the supernet's size times a bucket count past 2^32
+        // overflows u64.
+        let bound = |i: u64| base + (size as u128 * i as u128 / buckets as u128) as u64;
+        let mut density = vec![];
+        for i in 0..buckets as u64 {
+            let (lo, hi) = (bound(i), bound(i + 1));
+
+            if lo == hi {
+                density.push(0.0);
+                continue;
+            }
+
+            let hits: u64 = covered
+                .iter()
+                .map(|&(start, end)| end.min(hi).saturating_sub(start.max(lo)))
+                .sum();
+            density.push(hits as f64 / (hi - lo) as f64);
+        }
+
+        Ok(density)
+    }
+
+    fn covered_ranges(&self, idx: u32, covered: &mut Vec<(u64, u64)>) {
+        for node in outermost(self.nodes_from(idx).map(|(_, node)| node)) {
+            let start = node.network as u64;
+            covered.push((start, start + (1_u64 << (32 - node.prefix_len))));
+        }
+    }
+}
+
+/// Tables holding several equal-cost next-hops per prefix.
+impl<T, A: Address> Table<Vec<T>, A> {
+    /// Returns every next-hop of the longest matching prefix, in the order
+    /// they were inserted.
+    pub fn lookup_ecmp(&self, ip: impl IntoAddr<A>) -> Result<Option<&[T]>, RotabError> {
+        let ip = ip.into_addr()?.to_bits();
+
+        Ok(self
+            .longest_match(ip)
+            .and_then(|node| node.dest.as_deref())
+            .or(self.fallback.as_deref()))
+    }
+
+    /// Returns the first next-hop of the longest matching prefix.
+    pub fn lookup_first(&self, ip: impl IntoAddr<A>) -> Result<Option<T>, RotabError>
+    where
+        T: Clone,
+    {
+        Ok(self
+            .lookup_ecmp(ip)?
+            .and_then(|next_hops| next_hops.first().cloned()))
+    }
+
+    /// Treats the next-hops of the longest matching prefix as a primary
+    /// followed by backups in order, and returns the first one `is_alive`
+    /// accepts.
+    pub fn lookup_failover(
+        &self,
+        ip: impl IntoAddr<A>,
+        is_alive: impl Fn(&T) -> bool,
+    ) -> Result<Option<&T>, RotabError> {
+        Ok(self
+            .lookup_ecmp(ip)?
+            .and_then(|next_hops| next_hops.iter().find(|next_hop| is_alive(next_hop))))
+    }
+}
+
+/// Tables holding several next-hops per prefix, each with a weight.
+impl<T, A: Address> Table<Vec<(T, u16)>, A> {
+    /// Picks one next-hop of the longest matching prefix, with a chance
+    /// proportional to its weight. The choice depends only on `flow_hash`,
+    /// so packets of the same flow always take the same next-hop. Returns
+    /// `None` if every next-hop has weight zero.
+    pub fn lookup_weighted(
+        &self,
+        ip: impl IntoAddr<A>,
+        flow_hash: u32,
+    ) -> Result<Option<&T>, RotabError> {
+        let ip = ip.into_addr()?.to_bits();
+        let Some(next_hops) = self
+            .longest_match(ip)
+            .and_then(|node| node.dest.as_ref())
+            .or(self.fallback.as_ref())
+        else {
+            return Ok(None);
+        };
+
+        let total: u32 = next_hops.iter().map(|&(_, weight)| weight as u32).sum();
+        if total == 0 {
+            return Ok(None);
+        }
+
+        let mut pick = flow_hash % total;
+        for (next_hop, weight) in next_hops {
+            if pick < *weight as u32 {
+                return Ok(Some(next_hop));
+            }
+            pick -= *weight as u32;
+        }
+
+        unreachable!("pick is below the total weight")
+    }
+}
+
+/// Lists the routes one per line, as `network/prefix_len -> dest`, in prefix
+/// order.
+impl<V: fmt::Display, A: Address> fmt::Display for Table<V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (network, prefix_len, dest) in self.routes() {
+            writeln!(f, "{network}/{prefix_len} -> {dest}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Lists the routes as `(network, prefix_len, dest)` tuples, in prefix order.
+impl<V: fmt::Debug, A: Address> fmt::Debug for Table<V, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.routes()).finish()
+    }
+}
+
+/// Tables are equal if they hold the same routes with the same
+/// destinations and the same fallback, however they were built. Route
+/// metadata isn't compared.
+impl<V: PartialEq, A: Address> PartialEq for Table<V, A> {
+    fn eq(&self, other: &Self) -> bool {
+        // `routes` yields routes sorted by prefix, so equal sets of routes
+        // come out in the same order.
+        self.len == other.len && self.fallback == other.fallback && self.routes().eq(other.routes())
+    }
+}
+
+impl<V: Eq, A: Address> Eq for Table<V, A> {}
+
+pub struct Routes<'a, V, A: Address = Ipv4Addr> {
+    nodes: Nodes<'a, V, A::Bits>,
+    addr: PhantomData<A>,
+}
+
+impl<'a, V, A: Address> Iterator for Routes<'a, V, A> {
+    type Item = (A, u8, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nodes.find_map(|(_, node)| {
+            node.dest
+                .as_ref()
+                .filter(|_| node.is_terminal)
+                .map(|dest| (A::from_bits(node.network), node.prefix_len, dest))
+        })
+    }
+}
+
+impl<'a, V, A: Address> IntoIterator for &'a Table<V, A> {
+    type Item = (A, u8, &'a V);
+    type IntoIter = Routes<'a, V, A>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.routes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_table() -> RouteTable {
+        RouteTable::new()
+    }
+
+    fn bits<A: Address>(network: A::Bits, prefix_len: u8) -> Vec<u8> {
+        (0..prefix_len).map(|i| bit_at(network, i) as u8).collect()
+    }
+
+    #[test]
+    fn test_parse_lenient() {
+        assert_eq!(parse_lenient(" 10.0.0.1 "), Ok(Ipv4Addr::new(10, 0, 0, 1)));
+        assert_eq!(
+            parse_lenient("010.000.000.001"),
+            Ok(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(
+            parse_lenient("\t192.168.000.255\n"),
+            Ok(Ipv4Addr::new(192, 168, 0, 255))
+        );
+        assert_eq!(parse_lenient("0.0.0.0"), Ok(Ipv4Addr::UNSPECIFIED));
+
+        for invalid in [
+            "256.0.0.0",
+            "0256.0.0.0",
+            "10.0.0",
+            "10..0.1",
+            "10.0.0.1.",
+            "",
+        ] {
+            assert!(
+                matches!(parse_lenient(invalid), Err(RotabError::InvalidAddr(_))),
+                "{invalid}"
+            );
+        }
+
+        let mut table = create_table();
+        table
+            .insert_range_lenient(
+                " 010.000.001.000",
+                "010.000.001.255 ",
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+        assert!(table.contains_exact("10.0.1.0", "10.0.1.255").unwrap());
+        assert!(
+            table
+                .insert_range_lenient("256.0.0.0", "256.0.0.255", Ipv4Addr::new(192, 168, 0, 2))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_bit_at() {
+        let ip = Ipv4Addr::new(192, 168, 0, 1).to_bits();
+        assert_eq!(bit_at(ip, 0), 1);
+        assert_eq!(bit_at(ip, 2), 0);
+        assert_eq!(bit_at(ip, 12), 1);
+        assert_eq!(bit_at(ip, 13), 0);
+        assert_eq!(bit_at(ip, 31), 1);
+
+        assert_eq!(bit_at(0_u32, 31), 0);
+        assert_eq!(bit_at(u32::MAX, 0), 1);
+        assert_eq!(bit_at(1_u128, 127), 1);
+        assert_eq!(bit_at(1_u128 << 127, 0), 1);
+    }
+
+    #[test]
+    fn ip_to_bit_vec() {
+        let test_cases = vec![
+            (
+                "192.168.0.1",
+                vec![
+                    1, 1, 0, 0, 0, 0, 0, 0, 1, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0, 1,
+                ],
+            ),
+            ("0.0.0.0", vec![0; 32]),
+            ("255.255.255.255", vec![1; 32]),
+            (
+                "128.0.0.0",
+                vec![
+                    1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0, 0,
+                ],
+            ),
+            (
+                "0.0.0.1",
+                vec![
+                    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0, 1,
+                ],
+            ),
+            (
+                "10.0.0.0",
+                vec![
+                    0, 0, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0, 0,
+                ],
+            ),
+            (
+                "127.0.0.1",
+                vec![
+                    0, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+                    0, 0, 0, 0, 0, 1,
+                ],
+            ),
+            (
+                "1.2.3.4",
+                vec![
+                    0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0,
+                    0, 0, 0, 1, 0, 0,
+                ],
+            ),
+        ];
+
+        for (ip, expected) in test_cases {
+            let ip = Ipv4Addr::from_str(ip).unwrap().to_bits();
+            assert_eq!(expected.len(), 32);
+            for (i, &bit) in expected.iter().enumerate() {
+                let i = i as u8;
+                assert_eq!(bit_at(ip, i), bit, "{ip:#x} bit {i}");
+                // Masking to the bit keeps it as the lowest of the prefix.
+                assert_eq!(
+                    (ip & mask::<u32>(i + 1)) >> (31 - i) & 1,
+                    bit as u32,
+                    "{ip:#x} bit {i}"
+                );
+            }
+        }
+    }
+
+    const PREFIX_CASES: [(&str, &str, u8, &str); 22] = [
+        (
+            "192.168.1.1",
+            "192.168.1.1",
+            32,
+            "11000000101010000000000100000001",
+        ),
+        (
+            "192.168.0.0",
+            "192.168.0.255",
+            24,
+            "110000001010100000000000",
+        ),
+        ("10.0.0.0", "10.0.0.255", 24, "000010100000000000000000"),
+        (
+            "172.16.0.0",
+            "172.16.0.127",
+            25,
+            "1010110000010000000000000",
+        ),
+        (
+            "192.168.1.0",
+            "192.168.1.127",
+            25,
+            "1100000010101000000000010",
+        ),
+        ("10.1.0.0", "10.1.255.255", 16, "0000101000000001"),
+        (
+            "172.20.10.0",
+            "172.20.10.31",
+            27,
+            "101011000001010000001010000",
+        ),
+        (
+            "192.168.100.0",
+            "192.168.100.63",
+            26,
+            "11000000101010000110010000",
+        ),
+        ("10.10.0.0", "10.10.31.255", 19, "0000101000001010000"),
+        ("172.31.0.0", "172.31.15.255", 20, "10101100000111110000"),
+        (
+            "192.168.50.0",
+            "192.168.50.15",
+            28,
+            "1100000010101000001100100000",
+        ),
+        (
+            "192.168.1.1",
+            "192.168.1.1",
+            32,
+            "11000000101010000000000100000001",
+        ),
+        (
+            "192.168.2.0",
+            "192.168.2.1",
+            31,
+            "1100000010101000000000100000000",
+        ),
+        (
+            "192.168.3.0",
+            "192.168.3.3",
+            30,
+            "110000001010100000000011000000",
+        ),
+        (
+            "192.168.255.0",
+            "192.168.255.255",
+            24,
+            "110000001010100011111111",
+        ),
+        (
+            "192.168.4.0",
+            "192.168.4.7",
+            29,
+            "11000000101010000000010000000",
+        ),
+        (
+            "192.168.5.0",
+            "192.168.5.15",
+            28,
+            "1100000010101000000001010000",
+        ),
+        ("172.20.0.0", "172.20.255.255", 16, "1010110000010100"),
+        ("10.20.0.0", "10.20.1.255", 23, "00001010000101000000000"),
+        ("172.30.0.0", "172.30.3.255", 22, "1010110000011110000000"),
+        ("10.30.0.0", "10.30.7.255", 21, "000010100001111000000"),
+        ("0.0.0.0", "255.255.255.255", 0, ""),
+    ];
+
+    #[test]
+    fn prefix_length() {
+        for case in PREFIX_CASES {
+            let start = Ipv4Addr::from_str(case.0).unwrap().to_bits();
+            let end = Ipv4Addr::from_str(case.1).unwrap().to_bits();
+            let (network, prefix_len) = RouteTable::prefix(start, end);
+            assert_eq!(prefix_len, case.2);
+
+            let prefix_str = bits::<Ipv4Addr>(network, prefix_len)
+                .into_iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .concat();
+            assert_eq!(prefix_str, case.3)
+        }
+    }
+
+    #[test]
+    fn test_supernet_of() {
+        let addrs = [
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 0, 5),
+            Ipv4Addr::new(10, 0, 0, 9),
+        ];
+        assert_eq!(
+            RouteTable::supernet_of(&addrs),
+            Some((Ipv4Addr::new(10, 0, 0, 0), 28))
+        );
+        assert_eq!(
+            RouteTable::supernet_of(&[Ipv4Addr::new(10, 0, 0, 9), Ipv4Addr::new(10, 0, 0, 1)]),
+            Some((Ipv4Addr::new(10, 0, 0, 0), 28))
+        );
+        assert_eq!(
+            RouteTable::supernet_of(&[Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 1, 1)]),
+            Some((Ipv4Addr::new(10, 0, 0, 0), 23))
+        );
+        assert_eq!(
+            RouteTable::supernet_of(&[Ipv4Addr::new(192, 168, 0, 1)]),
+            Some((Ipv4Addr::new(192, 168, 0, 1), 32))
+        );
+        assert_eq!(
+            RouteTable::supernet_of(&[Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(192, 168, 0, 1)]),
+            Some((Ipv4Addr::new(0, 0, 0, 0), 0))
+        );
+        assert_eq!(RouteTable::supernet_of(&[]), None);
+
+        let addrs = ["2001:db8::1", "2001:db8::ff"].map(|s| Ipv6Addr::from_str(s).unwrap());
+        assert_eq!(
+            Table6::<()>::supernet_of(&addrs),
+            Some((Ipv6Addr::from_str("2001:db8::").unwrap(), 120))
+        );
+    }
+
+    #[test]
+    fn test_common_prefix() {
+        for (start, end, prefix_len, bits) in PREFIX_CASES {
+            let network = u32::from_str_radix(&format!("{bits:0<32}"), 2).unwrap();
+            assert_eq!(
+                RouteTable::common_prefix(
+                    Ipv4Addr::from_str(start).unwrap(),
+                    Ipv4Addr::from_str(end).unwrap()
+                ),
+                (network, prefix_len)
+            );
+        }
+
+        assert_eq!(
+            RouteTable::common_prefix(Ipv4Addr::new(10, 0, 0, 0), Ipv4Addr::new(10, 0, 1, 255)),
+            (0x0A00_0000, 23)
+        );
+        assert_eq!(
+            Table6::<()>::common_prefix(
+                Ipv6Addr::from_str("2001:db8::").unwrap(),
+                Ipv6Addr::from_str("2001:db8::ff").unwrap()
+            ),
+            (0x2001_0db8 << 96, 120)
+        );
+    }
+
+    #[test]
+    fn test_default_route() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "0.0.0.0".to_owned(),
+                "255.255.255.255".to_owned(),
+                Ipv4Addr::new(0, 0, 0, 0),
+            )
+            .unwrap();
+        assert_eq!(
+            table
+                .lookup("120.0.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "0.0.0.0"
+        );
+        assert_eq!(
+            table
+                .lookup("10.0.0.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "0.0.0.0"
+        );
+    }
+
+    #[test]
+    fn test_specific_prefixes() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "10.0.1.0".to_owned(),
+                "10.0.1.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "10.0.2.0".to_owned(),
+                "10.0.2.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 2),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "10.0.3.0".to_owned(),
+                "10.0.3.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 3),
+            )
+            .unwrap();
+        assert_eq!(
+            table
+                .lookup("10.0.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.1"
+        );
+        assert_eq!(
+            table
+                .lookup("10.0.2.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.2"
+        );
+        assert_eq!(
+            table
+                .lookup("10.0.3.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.3"
+        );
+    }
+
+    #[test]
+    fn test_overlapping_prefixes() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "0.0.0.0".to_owned(),
+                "127.255.255.255".to_owned(),
+                Ipv4Addr::new(1, 1, 1, 1),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "128.0.0.0".to_owned(),
+                "255.255.255.255".to_owned(),
+                Ipv4Addr::new(2, 2, 2, 2),
+            )
+            .unwrap();
+        assert_eq!(
+            table
+                .lookup("10.0.0.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "1.1.1.1"
+        );
+        assert_eq!(
+            table
+                .lookup("192.168.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "2.2.2.2"
+        );
+    }
+
+    #[test]
+    fn test_nested_prefixes() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "10.0.0.0".to_owned(),
+                "10.1.255.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 0),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "10.0.1.0".to_owned(),
+                "10.0.1.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+        assert_eq!(
+            table
+                .lookup("10.0.0.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.0"
+        );
+        assert_eq!(
+            table
+                .lookup("10.0.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.1"
+        );
+    }
+
+    #[test]
+    fn test_single_ip() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "192.168.1.1".to_owned(),
+                "192.168.1.1".to_owned(),
+                Ipv4Addr::new(192, 168, 1, 1),
+            )
+            .unwrap();
+        assert_eq!(
+            table
+                .lookup("192.168.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.1.1"
+        );
+        assert_eq!(table.lookup("192.168.1.2".to_owned()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_single_ip_matches_host_prefix() {
+        let hosts = [
+            "0.0.0.0",
+            "10.0.0.0",
+            "10.0.0.1",
+            "192.168.1.1",
+            "255.255.255.255",
+        ];
+
+        let mut from_range = Table::<usize>::new();
+        let mut from_u32 = Table::<usize>::new();
+        for (i, host) in hosts.into_iter().enumerate() {
+            assert_eq!(from_range.insert_range(host, host, i).unwrap(), None);
+            let addr = Ipv4Addr::from_str(host).unwrap().to_bits();
+            from_u32.insert_u32_prefix(addr, 32, i).unwrap();
+        }
+
+        assert_eq!(from_range, from_u32);
+        assert_eq!(from_range.prefix_len_histogram()[32], hosts.len());
+        assert_eq!(
+            from_range.insert_range("10.0.0.1", "10.0.0.1", 9).unwrap(),
+            Some(2)
+        );
+        assert_eq!(from_range.lookup("10.0.0.2").unwrap(), None);
+
+        let mut table = Table6::new();
+        table.insert_range("::", "::", 1).unwrap();
+        table.insert_range("2001:db8::", "2001:db8::", 2).unwrap();
+        assert_eq!(table.get_exact("::", 128).unwrap(), Some(1));
+        assert_eq!(table.get_exact("2001:db8::", 128).unwrap(), Some(2));
+        assert_eq!(table.lookup("2001:db8::1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_no_matching_prefix() {
+        let table = create_table();
+        assert_eq!(table.lookup("192.168.1.1".to_owned()).unwrap(), None);
+    }
+
+    #[test]
+    fn test_insertion_order() {
+        let mut table1 = create_table();
+        table1
+            .insert_range(
+                "10.0.0.0".to_owned(),
+                "10.1.255.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 0),
+            )
+            .unwrap();
+        table1
+            .insert_range(
+                "10.0.1.0".to_owned(),
+                "10.0.1.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+
+        let mut table2 = create_table();
+        table2
+            .insert_range(
+                "10.0.1.0".to_owned(),
+                "10.0.1.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+        table2
+            .insert_range(
+                "10.0.0.0".to_owned(),
+                "10.1.255.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 0),
+            )
+            .unwrap();
+
+        assert_eq!(
+            table1
+                .lookup("10.0.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.1"
+        );
+        assert_eq!(
+            table2
+                .lookup("10.0.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.1"
+        );
+        assert_eq!(
+            table1
+                .lookup("10.0.0.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.0"
+        );
+        assert_eq!(
+            table2
+                .lookup("10.0.0.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "192.168.0.0"
+        );
+    }
+
+    #[test]
+    fn test_for_each_mut() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "0.0.0.0".to_owned(),
+                "255.255.255.255".to_owned(),
+                Ipv4Addr::new(10, 0, 0, 1),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "10.0.1.0".to_owned(),
+                "10.0.1.255".to_owned(),
+                Ipv4Addr::new(10, 0, 0, 2),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "192.168.1.1".to_owned(),
+                "192.168.1.1".to_owned(),
+                Ipv4Addr::new(10, 0, 0, 3),
+            )
+            .unwrap();
+
+        let mut visited = vec![];
+        table.for_each_mut(|network, prefix_len, dest| {
+            visited.push((network.to_string(), prefix_len));
+            *dest = Ipv4Addr::from_bits(dest.to_bits() + 100);
+        });
+        visited.sort();
+        assert_eq!(
+            visited,
+            vec![
+                ("0.0.0.0".to_owned(), 0),
+                ("10.0.1.0".to_owned(), 24),
+                ("192.168.1.1".to_owned(), 32),
+            ]
+        );
+
+        assert_eq!(
+            table
+                .lookup("8.8.8.8".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "10.0.0.101"
+        );
+        assert_eq!(
+            table
+                .lookup("10.0.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "10.0.0.102"
+        );
+        assert_eq!(
+            table
+                .lookup("192.168.1.1".to_owned())
+                .unwrap()
+                .unwrap()
+                .to_string(),
+            "10.0.0.103"
+        );
+    }
+
+    #[test]
+    fn test_density() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "10.0.0.0".to_owned(),
+                "10.0.0.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "10.0.1.0".to_owned(),
+                "10.0.1.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "10.0.64.0".to_owned(),
+                "10.0.64.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 2),
+            )
+            .unwrap();
+
+        assert_eq!(
+            table.density("10.0.0.0/16", 4).unwrap(),
+            vec![2.0 / 64.0, 1.0 / 64.0, 0.0, 0.0]
+        );
+        assert_eq!(table.density("10.0.0.0/23", 2).unwrap(), vec![1.0, 1.0]);
+        assert_eq!(table.density("10.1.0.0/16", 2).unwrap(), vec![0.0, 0.0]);
+        assert_eq!(table.density("10.0.0.0/16", 0).unwrap(), Vec::<f64>::new());
+
+        // Buckets of the whole address space, 2^32 addresses for one.
+        assert_eq!(
+            table.density("0.0.0.0/0", 1).unwrap(),
+            vec![768.0 / 4_294_967_296.0]
+        );
+        assert_eq!(
+            table.density("0.0.0.0/0", 4).unwrap(),
+            vec![768.0 / 1_073_741_824.0, 0.0, 0.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_density_covering_route() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                "10.0.0.0".to_owned(),
+                "10.255.255.255".to_owned(),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+
+        assert_eq!(table.density("10.1.0.0/16", 3).unwrap(), vec![1.0; 3]);
+    }
+
+    #[test]
+    fn test_density_invalid_supernet() {
+        let table = create_table();
+
+        assert_eq!(
+            table.density("10.0.0.0", 4),
+            Err(RotabError::InvalidCidr("10.0.0.0".to_owned()))
+        );
+        assert_eq!(
+            table.density("10.0.0.0/33", 4),
+            Err(RotabError::InvalidPrefixLen(33))
+        );
+        assert!(matches!(
+            table.density("10.0.0/16", 4),
+            Err(RotabError::InvalidAddr(_))
+        ));
+    }
+
+    #[test]
+    fn test_str_entry_points() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.lookup("10.0.2.1").unwrap(), None);
+        assert!(table.lookup("10.0.1").is_err());
+        assert!(
+            table
+                .insert_range("10.0.2.0", "10.0.2", Ipv4Addr::new(192, 168, 0, 2))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_ipv4addr_entry_points() {
+        let mut table = create_table();
+        table
+            .insert_range(
+                Ipv4Addr::new(10, 0, 1, 0),
+                Ipv4Addr::new(10, 0, 1, 255),
+                Ipv4Addr::new(192, 168, 0, 1),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                Ipv4Addr::new(10, 0, 2, 0),
+                "10.0.2.255",
+                Ipv4Addr::new(192, 168, 0, 2),
+            )
+            .unwrap();
+
+        assert_eq!(
+            table.lookup(Ipv4Addr::new(10, 0, 1, 1)).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(table.lookup(Ipv4Addr::new(10, 0, 3, 1)).unwrap(), None);
+    }
+
+    #[test]
+    fn range_to_blocks() {
+        let ip = |s: &str| Ipv4Addr::from_str(s).unwrap().to_bits();
+        let test_cases = vec![
+            (
+                "10.0.0.0",
+                "10.0.0.5",
+                vec![("10.0.0.0", "10.0.0.3"), ("10.0.0.4", "10.0.0.5")],
+            ),
+            (
+                "10.0.0.1",
+                "10.0.0.14",
+                vec![
+                    ("10.0.0.1", "10.0.0.1"),
+                    ("10.0.0.2", "10.0.0.3"),
+                    ("10.0.0.4", "10.0.0.7"),
+                    ("10.0.0.8", "10.0.0.11"),
+                    ("10.0.0.12", "10.0.0.13"),
+                    ("10.0.0.14", "10.0.0.14"),
+                ],
+            ),
+            ("10.0.0.0", "10.0.0.255", vec![("10.0.0.0", "10.0.0.255")]),
+            (
+                "0.0.0.0",
+                "255.255.255.255",
+                vec![("0.0.0.0", "255.255.255.255")],
+            ),
+            (
+                "192.168.1.1",
+                "192.168.1.1",
+                vec![("192.168.1.1", "192.168.1.1")],
+            ),
+        ];
+
+        for (start, end, expected) in test_cases {
+            let expected = expected
+                .into_iter()
+                .map(|(first, last)| (ip(first), ip(last)))
+                .collect::<Vec<_>>();
+            assert_eq!(RouteTable::range_to_blocks(ip(start), ip(end)), expected);
+        }
+    }
+
+    #[test]
+    fn test_unaligned_range() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.0.0", "10.0.0.5", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        for ip in ["10.0.0.0", "10.0.0.3", "10.0.0.4", "10.0.0.5"] {
+            assert_eq!(
+                table.lookup(ip).unwrap(),
+                Some(Ipv4Addr::new(192, 168, 0, 1))
+            );
+        }
+        assert_eq!(table.lookup("10.0.0.6").unwrap(), None);
+        assert_eq!(table.lookup("10.0.0.7").unwrap(), None);
+    }
+
+    #[test]
+    fn test_unaligned_range_both_ends() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.0.1", "10.0.0.14", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        for i in 1..=14 {
+            assert_eq!(
+                table.lookup(Ipv4Addr::new(10, 0, 0, i)).unwrap(),
+                Some(Ipv4Addr::new(192, 168, 0, 1))
+            );
+        }
+        assert_eq!(table.lookup("10.0.0.0").unwrap(), None);
+        assert_eq!(table.lookup("10.0.0.15").unwrap(), None);
+    }
+
+    #[test]
+    fn test_range_crossing_octet_boundary() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.0.250", "10.0.1.5", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        for ip in ["10.0.0.250", "10.0.0.255", "10.0.1.0", "10.0.1.5"] {
+            assert_eq!(
+                table.lookup(ip).unwrap(),
+                Some(Ipv4Addr::new(192, 168, 0, 1))
+            );
+        }
+        assert_eq!(table.lookup("10.0.0.249").unwrap(), None);
+        assert_eq!(table.lookup("10.0.1.6").unwrap(), None);
+    }
+
+    #[test]
+    fn test_remove_range() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.0.0", "10.255.255.255", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_range("10.0.0.0", "10.0.255.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert!(table.remove_range("10.0.1.0", "10.0.1.255").unwrap());
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        assert!(table.remove_range("10.0.0.0", "10.0.255.255").unwrap());
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+
+        assert!(!table.remove_range("10.0.0.0", "10.0.255.255").unwrap());
+        assert!(!table.remove_range("172.16.0.0", "172.16.0.255").unwrap());
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_subtract() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert!(table.subtract("10.0.1.0/24").unwrap());
+        assert_eq!(table.lookup("10.0.1.5").unwrap(), None);
+        assert_eq!(
+            table.lookup("10.0.2.5").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.covered_address_count(), (1 << 24) - 256);
+        assert_eq!(table.len(), 16);
+        assert!(!table.subtract("10.0.1.0/24").unwrap());
+
+        // Routes under the hole go, and more specific routes next to it, or
+        // from a longer covering route, win over what the /0 is split into.
+        let mut table = create_table();
+        let cidrs = [
+            ("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0)),
+            ("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.128.0.0/9", Ipv4Addr::new(192, 168, 0, 2)),
+            ("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 3)),
+        ];
+        for (cidr, dest) in cidrs {
+            table.insert_cidr(cidr, dest).unwrap();
+        }
+        let before = table.clone();
+
+        assert!(table.subtract("10.0.1.7/24").unwrap());
+        assert_eq!(
+            before.coverage_diff(&table),
+            vec![
+                (
+                    (Ipv4Addr::new(10, 0, 1, 0), 25),
+                    CoverageState::OnlyThis(Ipv4Addr::new(192, 168, 0, 1))
+                ),
+                (
+                    (Ipv4Addr::new(10, 0, 1, 128), 25),
+                    CoverageState::OnlyThis(Ipv4Addr::new(192, 168, 0, 3))
+                ),
+            ]
+        );
+        assert_eq!(
+            table.get_exact("10.128.0.0", 9).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(table.max_prefix_len(), 24);
+
+        assert!(table.subtract("0.0.0.0/0").unwrap());
+        assert!(table.is_empty());
+        assert_eq!(node_count(&table), 1);
+    }
+
+    #[test]
+    fn test_remove_range_prunes_nodes() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_range("10.0.0.0", "10.0.0.5", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert!(table.remove_range("10.0.1.0", "10.0.1.255").unwrap());
+        assert!(table.remove_range("10.0.0.0", "10.0.0.5").unwrap());
+
+        assert_eq!(table.node(ROOT).children().count(), 0);
+        assert_eq!(table.free.len(), table.nodes.len() - 1);
+        assert_eq!(table.lookup("10.0.1.1").unwrap(), None);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_generic_dest() {
+        let mut table: Table<String> = Table::new();
+        table
+            .insert_range("0.0.0.0", "255.255.255.255", "default".to_owned())
+            .unwrap();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", "eth1".to_owned())
+            .unwrap();
+
+        assert_eq!(table.lookup("10.0.1.1").unwrap().as_deref(), Some("eth1"));
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap().as_deref(),
+            Some("default")
+        );
+
+        let mut table: Table<u32> = Table::new();
+        table.insert_range("10.0.0.0", "10.0.0.255", 3_u32).unwrap();
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(3));
+        assert_eq!(table.lookup("10.0.1.1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_insert_cidr() {
+        let mut table = create_table();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.2.1/32", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup("8.8.8.8").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+        assert_eq!(
+            table.lookup("10.0.1.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            table.lookup("10.0.2.2").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_insert_cidr_host_bits_set() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.5/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup("10.0.0.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.lookup("10.0.1.0").unwrap(), None);
+    }
+
+    #[test]
+    fn test_insert_cidr_invalid() {
+        let mut table = create_table();
+
+        assert_eq!(
+            table.insert_cidr("10.0.0.0/33", Ipv4Addr::new(192, 168, 0, 1)),
+            Err(RotabError::InvalidPrefixLen(33))
+        );
+        assert_eq!(
+            table.insert_cidr("10.0.0.0/", Ipv4Addr::new(192, 168, 0, 1)),
+            Err(RotabError::InvalidCidr("10.0.0.0/".to_owned()))
+        );
+        assert_eq!(
+            table.insert_cidr("10.0.0.0", Ipv4Addr::new(192, 168, 0, 1)),
+            Err(RotabError::InvalidCidr("10.0.0.0".to_owned()))
+        );
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_lookup_detailed() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup_detailed("10.0.1.1").unwrap(),
+            Some(Match {
+                dest: Ipv4Addr::new(192, 168, 0, 1),
+                prefix_len: 24,
+                network: Ipv4Addr::new(10, 0, 1, 0),
+            })
+        );
+        assert_eq!(
+            table.lookup_detailed("10.2.3.4").unwrap(),
+            Some(Match {
+                dest: Ipv4Addr::new(192, 168, 0, 0),
+                prefix_len: 8,
+                network: Ipv4Addr::new(10, 0, 0, 0),
+            })
+        );
+        assert_eq!(table.lookup_detailed("11.0.0.1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_lookup_with_backup() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/28", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        let (primary, backup) = table.lookup_with_backup("10.0.1.100").unwrap();
+        assert_eq!(
+            primary.map(|m| (m.network, m.prefix_len, m.dest)),
+            Some((
+                Ipv4Addr::new(10, 0, 1, 0),
+                24,
+                Ipv4Addr::new(192, 168, 0, 1)
+            ))
+        );
+        assert_eq!(
+            backup.map(|m| (m.network, m.prefix_len, m.dest)),
+            Some((Ipv4Addr::new(10, 0, 0, 0), 8, Ipv4Addr::new(192, 168, 0, 0)))
+        );
+
+        // Only the next shorter route is the backup, not the shortest.
+        let (primary, backup) = table.lookup_with_backup("10.0.1.1").unwrap();
+        assert_eq!(primary.map(|m| m.prefix_len), Some(28));
+        assert_eq!(backup.map(|m| m.prefix_len), Some(24));
+
+        let (primary, backup) = table.lookup_with_backup("10.2.0.1").unwrap();
+        assert_eq!(primary.map(|m| m.prefix_len), Some(8));
+        assert_eq!(backup, None);
+
+        assert_eq!(table.lookup_with_backup("11.0.0.1").unwrap(), (None, None));
+    }
+
+    #[test]
+    fn test_lookup_with_backup_preferred() {
+        let preferred = RouteMeta {
+            preferred: true,
+            ..RouteMeta::default()
+        };
+
+        let mut table = Table::<u32>::new();
+        table
+            .insert_range_with_meta("10.0.0.0", "10.255.255.255", 1, preferred)
+            .unwrap();
+        table.insert_cidr("10.0.0.0/16", 2).unwrap();
+        table.insert_cidr("10.0.0.0/24", 3).unwrap();
+
+        // Withdrawing the preferred /8 hands over to the /24, not the /16.
+        let (primary, backup) = table.lookup_with_backup("10.0.0.1").unwrap();
+        assert_eq!(primary.map(|m| (m.prefix_len, m.dest)), Some((8, 1)));
+        assert_eq!(backup.map(|m| (m.prefix_len, m.dest)), Some((24, 3)));
+
+        // A preferred route is the backup of a longer preferred route.
+        table
+            .insert_range_with_meta("10.0.0.0", "10.0.0.15", 4, preferred)
+            .unwrap();
+        let (primary, backup) = table.lookup_with_backup("10.0.0.1").unwrap();
+        assert_eq!(primary.map(|m| m.prefix_len), Some(28));
+        assert_eq!(backup.map(|m| m.prefix_len), Some(8));
+    }
+
+    #[test]
+    fn test_lookup_str() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup_str("10.0.0.1").unwrap(),
+            Some("192.168.0.1".to_string())
+        );
+        assert_eq!(table.lookup_str("11.0.0.1").unwrap(), None);
+        assert!(table.lookup_str("10.0.0").is_err());
+
+        let mut table = ActionTable::new();
+        table
+            .insert_cidr("10.0.0.0/8", RouteAction::Blackhole)
+            .unwrap();
+        assert_eq!(
+            table.lookup_str("10.0.0.1").unwrap(),
+            Some(RouteAction::<Ipv4Addr>::Blackhole.to_string())
+        );
+    }
+
+    #[test]
+    fn test_lookup_detailed_default_route() {
+        let mut table = create_table();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup_detailed("10.0.1.1").unwrap(),
+            Some(Match {
+                dest: Ipv4Addr::new(192, 168, 0, 0),
+                prefix_len: 0,
+                network: Ipv4Addr::new(0, 0, 0, 0),
+            })
+        );
+    }
+
+    #[test]
+    fn test_table_is_send_sync() {
+        fn assert_send<T: Send>() {}
+        fn assert_sync<T: Sync>() {}
+
+        assert_send::<RouteTable>();
+        assert_sync::<RouteTable>();
+    }
+
+    #[test]
+    fn test_many_routes() {
+        let mut table = create_table();
+        for i in 0..10_000_u32 {
+            let network = Ipv4Addr::from_bits(0x0A00_0000 + (i << 8));
+            table
+                .insert_range(
+                    network,
+                    Ipv4Addr::from_bits(network.to_bits() + 255),
+                    Ipv4Addr::from_bits(i),
+                )
+                .unwrap();
+        }
+
+        for k in 0..100_000_u32 {
+            let offset = k.wrapping_mul(7919) % (10_256 << 8);
+            let expected = (offset < 10_000 << 8).then(|| Ipv4Addr::from_bits(offset >> 8));
+
+            assert_eq!(
+                table
+                    .lookup(Ipv4Addr::from_bits(0x0A00_0000 + offset))
+                    .unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_remove_range_reuses_nodes() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        let len = table.nodes.len();
+
+        assert!(table.remove_range("10.0.1.0", "10.0.1.255").unwrap());
+        table
+            .insert_cidr("10.0.2.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(table.nodes.len(), len);
+        assert_eq!(table.lookup("10.0.1.1").unwrap(), None);
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+    }
+
+    fn node_count<V, A: Address>(table: &Table<V, A>) -> usize {
+        table.nodes.len() - table.free.len()
+    }
+
+    #[test]
+    fn test_validate() {
+        let empty = create_table();
+        assert_eq!(empty.validate(), Ok(()));
+        assert_eq!(create_table6().validate(), Ok(()));
+
+        let mut table = create_table();
+        for (i, &(start, end, _, _)) in PREFIX_CASES.iter().enumerate() {
+            table
+                .insert_range(start, end, Ipv4Addr::new(192, 168, 0, i as u8))
+                .unwrap();
+            assert_eq!(table.validate(), Ok(()));
+        }
+        for &(start, end, _, _) in PREFIX_CASES.iter().step_by(2) {
+            table.remove_range(start, end).unwrap();
+            assert_eq!(table.validate(), Ok(()));
+        }
+        for (start, end, _, _) in PREFIX_CASES {
+            table
+                .insert_range(start, end, Ipv4Addr::new(192, 168, 1, 1))
+                .unwrap();
+        }
+        assert_eq!(table.validate(), Ok(()));
+
+        table.aggregate();
+        assert_eq!(table.validate(), Ok(()));
+        table.prune_redundant();
+        assert_eq!(table.validate(), Ok(()));
+        table.subtract("10.0.1.0/24").unwrap();
+        assert_eq!(table.validate(), Ok(()));
+        table.compact();
+        assert_eq!(table.validate(), Ok(()));
+        table.clear();
+        assert_eq!(table.validate(), Ok(()));
+
+        let mut table = Table::<Ipv4Addr>::new_auto_aggregate();
+        for cidr in ["10.0.0.0/25", "10.0.0.128/25", "10.0.1.0/24", "10.0.2.0/24"] {
+            table
+                .insert_cidr(cidr, Ipv4Addr::new(192, 168, 0, 1))
+                .unwrap();
+            assert_eq!(table.validate(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_validate_reports_violations() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        let mut broken = table.clone();
+        broken.len += 1;
+        assert_eq!(
+            broken.validate(),
+            Err("len is 3 but 2 routes exist".to_owned())
+        );
+
+        let mut broken = table.clone();
+        let leaf = broken.find_node(0x0A00_0100, 24).unwrap();
+        broken.node_mut(leaf).is_terminal = false;
+        assert_eq!(
+            broken.validate(),
+            Err("10.0.1.0/24 is terminal without a destination".to_owned())
+        );
+        broken.node_mut(leaf).dest = None;
+        assert_eq!(
+            broken.validate(),
+            Err("10.0.1.0/24 has no route and doesn't branch".to_owned())
+        );
+
+        let mut broken = table.clone();
+        let branch = broken.find_node(0x0A00_0000, 23).unwrap();
+        broken.node_mut(branch).edges.swap(0, 1);
+        assert_eq!(
+            broken.validate(),
+            Err("10.0.1.0/24 is on edge 0 under 10.0.0.0/23".to_owned())
+        );
+
+        let mut broken = table.clone();
+        broken.free.push(leaf);
+        assert!(
+            broken
+                .validate()
+                .unwrap_err()
+                .contains("is in use but free")
+        );
+    }
+
+    #[test]
+    fn test_path_compression() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.1/32", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("192.168.1.1/32", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(node_count(&table), 3);
+
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("192.168.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        for ip in [
+            "10.0.0.0",
+            "10.0.0.3",
+            "138.0.0.1",
+            "192.168.1.0",
+            "192.168.1.129",
+        ] {
+            assert_eq!(table.lookup(ip).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn test_path_compression_split() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/24", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        // root, the two /24s and the /23 they branch from
+        assert_eq!(node_count(&table), 4);
+
+        table
+            .insert_cidr("10.0.0.0/23", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/16", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        assert_eq!(node_count(&table), 5);
+
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+
+        assert!(table.remove_range("10.0.0.0", "10.0.1.255").unwrap());
+        assert!(table.remove_range("10.0.1.0", "10.0.1.255").unwrap());
+        assert_eq!(node_count(&table), 3);
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("11.0.0.0/8", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(
+            table.to_dot(),
+            "digraph rotab {
+    node [shape=box];
+    n0 [label=\"*\"];
+    n0 -> n2 [label=\"0\"];
+    n2 [label=\"0000101\"];
+    n2 -> n1 [label=\"0\"];
+    n2 -> n3 [label=\"1\"];
+    n1 [label=\"00001010\\n10.0.0.0/8 -> 192.168.0.1\", style=filled];
+    n3 [label=\"00001011\\n11.0.0.0/8 -> 192.168.0.2\", style=filled];
+}
+"
+        );
+
+        assert_eq!(
+            create_table().to_dot(),
+            "digraph rotab {\n    node [shape=box];\n    n0 [label=\"*\"];\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_replace_next_hop() {
+        let a = Ipv4Addr::new(192, 168, 0, 1);
+        let b = Ipv4Addr::new(192, 168, 0, 2);
+        let c = Ipv4Addr::new(192, 168, 0, 3);
+
+        let mut table = create_table();
+        table.insert_cidr("10.0.0.0/8", a).unwrap();
+        table.insert_cidr("10.0.1.0/24", b).unwrap();
+        table.insert_cidr("172.16.0.0/12", a).unwrap();
+
+        assert_eq!(table.replace_next_hop(a, c), 2);
+        assert_eq!(table.lookup("10.9.0.1").unwrap(), Some(c));
+        assert_eq!(table.lookup("172.16.0.1").unwrap(), Some(c));
+        assert_eq!(table.lookup("10.0.1.1").unwrap(), Some(b));
+
+        let generation = table.generation();
+        assert_eq!(table.replace_next_hop(a, c), 0);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.generation(), generation);
+
+        assert_eq!(table.replace_next_hop(b, a), 1);
+        assert!(table.generation() > generation);
+    }
+
+    #[test]
+    fn test_deep_traversals() {
+        // Every prefix of one address is a chain as deep as the trie gets.
+        let ip = Ipv6Addr::from_str("2001:db8:85a3::8a2e:370:7334").unwrap();
+        let mut table = Table6::<u32>::new();
+        for prefix_len in 0..=128 {
+            let network = ip.to_bits() & mask::<u128>(prefix_len);
+            table.insert_prefix(network, prefix_len, prefix_len as u32);
+        }
+        assert_eq!(table.len(), 129);
+
+        let stats = table.stats();
+        assert_eq!(stats.max_depth, 128);
+        assert_eq!(stats.node_count, 129);
+
+        assert_eq!(table.routes().count(), 129);
+        assert_eq!(table.to_string().lines().count(), 129);
+
+        let mut visited = 0;
+        table.for_each_mut(|_, prefix_len, dest| {
+            assert_eq!(*dest, prefix_len as u32);
+            *dest += 1;
+            visited += 1;
+        });
+        assert_eq!(visited, 129);
+        assert_eq!(table.lookup(ip).unwrap(), Some(129));
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        let mut routes = vec![];
+        for (network, prefix_len, &dest) in &table {
+            routes.push((network, prefix_len, dest));
+        }
+        assert_eq!(
+            routes,
+            vec![
+                (Ipv4Addr::new(0, 0, 0, 0), 0, Ipv4Addr::new(192, 168, 0, 0)),
+                (Ipv4Addr::new(10, 0, 0, 0), 8, Ipv4Addr::new(192, 168, 0, 1)),
+                (
+                    Ipv4Addr::new(10, 0, 1, 0),
+                    24,
+                    Ipv4Addr::new(192, 168, 0, 2)
+                ),
+            ]
+        );
+
+        let long = (&table)
+            .into_iter()
+            .filter(|&(_, prefix_len, _)| prefix_len >= 8)
+            .count();
+        assert_eq!(long, 2);
+    }
+
+    #[test]
+    fn test_for_each_route() {
+        let mut table = create_table();
+        let cidrs = [
+            ("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0)),
+            ("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2)),
+            ("192.168.1.1/32", Ipv4Addr::new(172, 16, 0, 9)),
+        ];
+        for (cidr, dest) in cidrs {
+            table.insert_cidr(cidr, dest).unwrap();
+        }
+
+        let mut sum = 0;
+        let mut count = 0;
+        table.for_each_route(|_, _, dest| {
+            sum += dest.octets().iter().map(|&o| o as u32).sum::<u32>();
+            count += 1;
+        });
+
+        let expected = cidrs
+            .iter()
+            .flat_map(|(_, dest)| dest.octets())
+            .map(|o| o as u32)
+            .sum::<u32>();
+        assert_eq!(sum, expected);
+        assert_eq!(count, 4);
+
+        let mut visited = vec![];
+        table.for_each_route(|network, prefix_len, _| visited.push((network, prefix_len)));
+        assert_eq!(
+            visited,
+            table
+                .routes()
+                .map(|(network, prefix_len, _)| (network, prefix_len))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_routes() {
+        let mut table = create_table();
+        let cidrs = [
+            ("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2)),
+            ("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0)),
+            ("192.168.1.1/32", Ipv4Addr::new(192, 168, 0, 4)),
+            ("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.0.0.5/25", Ipv4Addr::new(192, 168, 0, 3)),
+        ];
+        for (cidr, dest) in cidrs {
+            table.insert_cidr(cidr, dest).unwrap();
+        }
+
+        let expected = vec![
+            (Ipv4Addr::new(0, 0, 0, 0), 0, Ipv4Addr::new(192, 168, 0, 0)),
+            (Ipv4Addr::new(10, 0, 0, 0), 8, Ipv4Addr::new(192, 168, 0, 1)),
+            (
+                Ipv4Addr::new(10, 0, 0, 0),
+                25,
+                Ipv4Addr::new(192, 168, 0, 3),
+            ),
+            (
+                Ipv4Addr::new(10, 0, 1, 0),
+                24,
+                Ipv4Addr::new(192, 168, 0, 2),
+            ),
+            (
+                Ipv4Addr::new(192, 168, 1, 1),
+                32,
+                Ipv4Addr::new(192, 168, 0, 4),
+            ),
+        ];
+
+        let routes = table
+            .routes()
+            .map(|(network, prefix_len, dest)| (network, prefix_len, *dest))
+            .collect::<Vec<_>>();
+        assert_eq!(routes, expected);
+
+        let mut sorted = routes.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+    }
+
+    #[test]
+    fn test_routes_empty() {
+        let table = create_table();
+        assert_eq!(table.routes().count(), 0);
+    }
+
+    #[test]
+    fn test_len() {
+        let mut table = create_table();
+        assert_eq!(table.len(), 0);
+        assert!(table.is_empty());
+
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_range("10.1.0.0", "10.1.0.5", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        assert_eq!(table.len(), 4);
+        assert!(!table.is_empty());
+
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 4))
+            .unwrap();
+        assert_eq!(table.len(), 4);
+
+        assert!(table.remove_range("10.1.0.0", "10.1.0.5").unwrap());
+        assert_eq!(table.len(), 2);
+        assert!(!table.remove_range("10.1.0.0", "10.1.0.5").unwrap());
+        assert_eq!(table.len(), 2);
+
+        assert!(table.remove_range("10.0.0.0", "10.255.255.255").unwrap());
+        assert!(table.remove_range("10.0.1.0", "10.0.1.255").unwrap());
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut table = create_table();
+        assert_eq!(table.generation(), 0);
+
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        let inserted = table.generation();
+        assert!(inserted > 0);
+
+        table.lookup("10.0.0.1").unwrap();
+        table.lookup_all("10.0.0.1").unwrap();
+        assert!(!table.remove_range("10.0.1.0", "10.0.1.255").unwrap());
+        table.compact();
+        assert_eq!(table.generation(), inserted);
+
+        assert!(table.remove_range("10.0.0.0", "10.0.0.255").unwrap());
+        let removed = table.generation();
+        assert!(removed > inserted);
+
+        table
+            .entry("10.0.0.0", "10.0.0.255")
+            .unwrap()
+            .or_insert(Ipv4Addr::new(192, 168, 0, 2));
+        assert!(table.generation() > removed);
+
+        let modified = table.generation();
+        table.replace_next_hop(Ipv4Addr::new(192, 168, 0, 2), Ipv4Addr::new(192, 168, 0, 3));
+        assert!(table.generation() > modified);
+
+        let snapshot = table.snapshot();
+        let snapshotted = table.generation();
+        table.clear();
+        assert!(table.generation() > snapshotted);
+
+        let cleared = table.generation();
+        table.restore(&snapshot);
+        assert!(table.generation() > cleared);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_max_prefix_len() {
+        let mut table = create_table();
+        assert_eq!(table.max_prefix_len(), 0);
+
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.1/32", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        table
+            .insert_cidr("10.0.2.0/24", Ipv4Addr::new(192, 168, 0, 4))
+            .unwrap();
+        assert_eq!(table.max_prefix_len(), 32);
+
+        assert!(table.remove_cidr("10.0.1.1/32").unwrap());
+        assert_eq!(table.max_prefix_len(), 24);
+        assert!(table.remove_cidr("10.0.1.0/24").unwrap());
+        assert_eq!(table.max_prefix_len(), 24);
+        assert!(table.remove_cidr("10.0.2.0/24").unwrap());
+        assert_eq!(table.max_prefix_len(), 8);
+
+        table
+            .entry("10.1.0.0", "10.1.0.127")
+            .unwrap()
+            .or_insert(Ipv4Addr::new(192, 168, 0, 5));
+        assert_eq!(table.max_prefix_len(), 25);
+
+        table.clear();
+        assert_eq!(table.max_prefix_len(), 0);
+    }
+
+    #[test]
+    fn test_max_prefix_len_many_removals() {
+        // Removing a route of the maximum length doesn't rescan the table,
+        // so draining many /32s from a large table stays fast.
+        let mut table = Table::<u32>::with_capacity(100_001);
+        table.insert_cidr("10.0.0.0/8", 0).unwrap();
+        for i in 0..100_000_u32 {
+            table.insert_u32_prefix(0x0B00_0000 + i, 32, i).unwrap();
+        }
+
+        for i in 0..100_000_u32 {
+            assert_eq!(table.max_prefix_len(), 32);
+            table.remove_prefix(0x0B00_0000 + i, 32);
+        }
+        assert_eq!(table.max_prefix_len(), 8);
+        assert_eq!(table.prefix_len_histogram()[8], 1);
+        assert_eq!(table.validate(), Ok(()));
+    }
+
+    #[test]
+    fn test_insert_with_outcome() {
+        let mut table = create_table();
+        assert_eq!(
+            table
+                .insert_with_outcome("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+                .unwrap(),
+            InsertOutcome::Created
+        );
+        assert_eq!(table.len(), 1);
+
+        assert_eq!(
+            table
+                .insert_with_outcome("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+                .unwrap(),
+            InsertOutcome::Unchanged
+        );
+        assert_eq!(table.len(), 1);
+
+        assert_eq!(
+            table
+                .insert_with_outcome("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 2))
+                .unwrap(),
+            InsertOutcome::Updated(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.len(), 1);
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+
+        // A covering route doesn't make a nested prefix unchanged.
+        assert_eq!(
+            table
+                .insert_with_outcome("10.0.0.0", "10.0.0.127", Ipv4Addr::new(192, 168, 0, 2))
+                .unwrap(),
+            InsertOutcome::Created
+        );
+        assert!(matches!(
+            table.insert_with_outcome("10.0.1.0", "10.0.1.5", Ipv4Addr::new(192, 168, 0, 3)),
+            Err(RotabError::NotSingleCidr { .. })
+        ));
+    }
+
+    #[test]
+    fn test_insert_returns_previous_dest() {
+        let mut table = create_table();
+
+        assert_eq!(
+            table
+                .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            table
+                .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 2))
+                .unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table
+                .insert_cidr("10.0.0.0/24", Ipv4Addr::new(192, 168, 0, 3))
+                .unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            table
+                .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 4))
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            table
+                .insert_cidr("10.0.0.0/16", Ipv4Addr::new(192, 168, 0, 5))
+                .unwrap(),
+            None
+        );
+
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn test_error_variants() {
+        let mut table = create_table();
+
+        assert!(matches!(
+            table.lookup("10.0.0.256"),
+            Err(RotabError::InvalidAddr(_))
+        ));
+        assert!(matches!(
+            table.insert_range("10.0.0.0", "10.0.0", Ipv4Addr::new(192, 168, 0, 1)),
+            Err(RotabError::InvalidAddr(_))
+        ));
+        assert_eq!(
+            table.remove_range("10.0.0.255", "10.0.0.0"),
+            Err(RotabError::InvertedRange {
+                start: Ipv4Addr::new(10, 0, 0, 255).into(),
+                end: Ipv4Addr::new(10, 0, 0, 0).into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_inverted_range() {
+        let mut table = create_table();
+
+        assert_eq!(
+            table.insert_range("10.0.0.255", "10.0.0.0", Ipv4Addr::new(192, 168, 0, 1)),
+            Err(RotabError::InvertedRange {
+                start: Ipv4Addr::new(10, 0, 0, 255).into(),
+                end: Ipv4Addr::new(10, 0, 0, 0).into(),
+            })
+        );
+        assert!(table.is_empty());
+        assert_eq!(table.routes().count(), 0);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), None);
+    }
+
+    fn create_table6() -> Table6<Ipv6Addr> {
+        let mut table = Table6::new();
+        table
+            .insert_range(
+                "2001:db8::",
+                "2001:db8:ffff:ffff:ffff:ffff:ffff:ffff",
+                Ipv6Addr::from_str("fe80::1").unwrap(),
+            )
+            .unwrap();
+        table
+            .insert_range(
+                "2001:db8:1::",
+                "2001:db8:1:ffff:ffff:ffff:ffff:ffff",
+                Ipv6Addr::from_str("fe80::2").unwrap(),
+            )
+            .unwrap();
+        table
+    }
+
+    #[test]
+    fn test_v6_prefix_length() {
+        let start = Ipv6Addr::from_str("2001:db8::").unwrap().to_bits();
+        let end = Ipv6Addr::from_str("2001:db8:ffff:ffff:ffff:ffff:ffff:ffff")
+            .unwrap()
+            .to_bits();
+        assert_eq!(Table6::<()>::prefix(start, end), (0x2001_0db8 << 96, 32));
+
+        let bits = bits::<Ipv6Addr>(1, 128);
+        assert_eq!(bits.len(), 128);
+        assert_eq!(bits.iter().filter(|&&b| b == 1).count(), 1);
+        assert_eq!(bits[127], 1);
+    }
+
+    #[test]
+    fn test_v6_default_route() {
+        let mut table = create_table6();
+        assert_eq!(table.lookup("2001:db9::1").unwrap(), None);
+
+        table
+            .insert_range(
+                "::",
+                "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff",
+                Ipv6Addr::from_str("fe80::ff").unwrap(),
+            )
+            .unwrap();
+        assert_eq!(
+            table.lookup("2001:db9::1").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::ff").unwrap())
+        );
+        assert_eq!(
+            table.lookup("::").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::ff").unwrap())
+        );
+        assert_eq!(
+            table.lookup("2001:db8::1").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::1").unwrap())
+        );
+    }
+
+    #[test]
+    fn test_v6_specific_and_nested() {
+        let table = create_table6();
+        assert_eq!(
+            table.lookup("2001:db8:1::42").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::2").unwrap())
+        );
+        assert_eq!(
+            table.lookup("2001:db8:2::42").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::1").unwrap())
+        );
+        assert_eq!(
+            table
+                .lookup(Ipv6Addr::from_str("2001:db8:ffff::1").unwrap())
+                .unwrap(),
+            Some(Ipv6Addr::from_str("fe80::1").unwrap())
+        );
+        assert_eq!(table.lookup("2001:db7:ffff::1").unwrap(), None);
+
+        let m = table.lookup_detailed("2001:db8:1::42").unwrap().unwrap();
+        assert_eq!(m.prefix_len, 48);
+        assert_eq!(m.network, Ipv6Addr::from_str("2001:db8:1::").unwrap());
+    }
+
+    #[test]
+    fn test_v6_host_route() {
+        let mut table = create_table6();
+        table
+            .insert_cidr("2001:db8:1::1/128", Ipv6Addr::from_str("fe80::3").unwrap())
+            .unwrap();
+
+        assert_eq!(
+            table.lookup("2001:db8:1::1").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::3").unwrap())
+        );
+        assert_eq!(
+            table.lookup("2001:db8:1::2").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::2").unwrap())
+        );
+        assert_eq!(
+            table.lookup("2001:db8:1::").unwrap(),
+            Some(Ipv6Addr::from_str("fe80::2").unwrap())
+        );
+
+        let m = table.lookup_detailed("2001:db8:1::1").unwrap().unwrap();
+        assert_eq!(m.prefix_len, 128);
+    }
+
+    #[test]
+    fn test_v6_unaligned_range() {
+        let mut table = Table6::new();
+        table.insert_range("2001:db8::1", "2001:db8::6", 1).unwrap();
+        assert_eq!(table.len(), 4);
+
+        assert_eq!(table.lookup("2001:db8::").unwrap(), None);
+        for ip in ["2001:db8::1", "2001:db8::2", "2001:db8::5", "2001:db8::6"] {
+            assert_eq!(table.lookup(ip).unwrap(), Some(1));
+        }
+        assert_eq!(table.lookup("2001:db8::7").unwrap(), None);
+
+        assert!(table.remove_range("2001:db8::1", "2001:db8::6").unwrap());
+        assert!(table.is_empty());
+        assert_eq!(node_count(&table), 1);
+    }
+
+    #[test]
+    fn test_v6_range_to_blocks_top_of_space() {
+        let blocks = Table6::<()>::range_to_blocks(u128::MAX - 2, u128::MAX);
+        assert_eq!(
+            blocks,
+            vec![(u128::MAX - 2, u128::MAX - 2), (u128::MAX - 1, u128::MAX)]
+        );
+        assert_eq!(
+            Table6::<()>::range_to_blocks(0, u128::MAX),
+            vec![(0, u128::MAX)]
+        );
+    }
+
+    #[test]
+    fn test_v6_errors() {
+        let mut table = Table6::new();
+        assert!(matches!(
+            table.insert_cidr("2001:db8::/129", 1),
+            Err(RotabError::InvalidPrefixLen(129))
+        ));
+        assert!(matches!(
+            table.insert_cidr("10.0.0.0/8", 1),
+            Err(RotabError::InvalidAddr(_))
+        ));
+        assert_eq!(
+            table.insert_range("2001:db8::ff", "2001:db8::", 1),
+            Err(RotabError::InvertedRange {
+                start: Ipv6Addr::from_str("2001:db8::ff").unwrap().into(),
+                end: Ipv6Addr::from_str("2001:db8::").unwrap().into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_lookup_matches_linear_scan() {
+        let mut table = RouteTable::new();
+        let mut routes = vec![];
+        let mut seed = 0x2545_f491_u32;
+        for i in 0..2_000_u32 {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+
+            let prefix_len = (seed % 33) as u8;
+            let network = seed & mask::<u32>(prefix_len);
+            let dest = Ipv4Addr::from_bits(i);
+            table
+                .insert_range(
+                    Ipv4Addr::from_bits(network),
+                    Ipv4Addr::from_bits(network | !mask::<u32>(prefix_len)),
+                    dest,
+                )
+                .unwrap();
+            routes.retain(|&(n, l, _)| (n, l) != (network, prefix_len));
+            routes.push((network, prefix_len, dest));
+        }
+
+        for k in 0..20_000_u32 {
+            let ip = k.wrapping_mul(0x9e37_79b9);
+            let expected = routes
+                .iter()
+                .filter(|&&(network, prefix_len, _)| ip & mask::<u32>(prefix_len) == network)
+                .max_by_key(|&&(_, prefix_len, _)| prefix_len)
+                .map(|&(_, _, dest)| dest);
+
+            assert_eq!(table.lookup(Ipv4Addr::from_bits(ip)).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_insert_random_ranges() {
+        let mut table = RouteTable::new();
+        let edges = [
+            (0, 0),
+            (0, u32::MAX),
+            (u32::MAX, u32::MAX),
+            (u32::MAX - 1, u32::MAX),
+            (0, 1),
+            (1, u32::MAX - 1),
+        ];
+        for (start, end) in edges {
+            table
+                .insert_range(
+                    Ipv4Addr::from_bits(start),
+                    Ipv4Addr::from_bits(end),
+                    Ipv4Addr::UNSPECIFIED,
+                )
+                .unwrap();
+        }
+
+        let mut seed = 0x9e37_79b9_u32;
+        let mut next = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            seed
+        };
+
+        let mut hosts = vec![];
+        for i in 0..500_u32 {
+            let (a, b) = (next(), next());
+            table
+                .insert_range(
+                    Ipv4Addr::from_bits(a.min(b)),
+                    Ipv4Addr::from_bits(a.max(b)),
+                    Ipv4Addr::from_bits(i),
+                )
+                .unwrap();
+
+            let host = next();
+            let dest = Ipv4Addr::from_bits(!i);
+            table
+                .insert_range(Ipv4Addr::from_bits(host), Ipv4Addr::from_bits(host), dest)
+                .unwrap();
+            hosts.retain(|&(h, _)| h != host);
+            hosts.push((host, dest));
+        }
+
+        for (host, dest) in hosts {
+            assert_eq!(table.lookup(Ipv4Addr::from_bits(host)).unwrap(), Some(dest));
+        }
+    }
+
+    #[test]
+    fn test_covered_address_count() {
+        let mut table = create_table();
+        assert_eq!(table.covered_address_count(), 0);
+
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert_eq!(table.covered_address_count(), 1 << 24);
+
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("172.16.5.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert_eq!(table.covered_address_count(), 2 * 256);
+
+        table
+            .insert_cidr("172.16.5.7/32", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        table
+            .insert_cidr("192.168.1.1/32", Ipv4Addr::new(192, 168, 0, 4))
+            .unwrap();
+        assert_eq!(table.covered_address_count(), 2 * 256 + 1);
+
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 5))
+            .unwrap();
+        assert_eq!(table.covered_address_count(), 1 << 32);
+    }
+
+    #[test]
+    fn test_lookup_ecmp() {
+        let next_hops = vec![
+            Ipv4Addr::new(192, 168, 0, 1),
+            Ipv4Addr::new(192, 168, 0, 2),
+            Ipv4Addr::new(192, 168, 0, 3),
+        ];
+
+        let mut table = EcmpTable::new();
+        table
+            .insert_range("0.0.0.0", "255.255.255.255", vec![])
+            .unwrap();
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", next_hops.clone())
+            .unwrap();
+
+        assert_eq!(
+            table.lookup_ecmp("10.0.0.1").unwrap(),
+            Some(next_hops.as_slice())
+        );
+        assert_eq!(
+            table.lookup_first("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        assert_eq!(table.lookup_ecmp("10.0.1.1").unwrap(), Some(&[][..]));
+        assert_eq!(table.lookup_first("10.0.1.1").unwrap(), None);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(next_hops));
+    }
+
+    #[test]
+    fn test_lookup_failover() {
+        let a = Ipv4Addr::new(192, 168, 0, 1);
+        let b = Ipv4Addr::new(192, 168, 0, 2);
+        let c = Ipv4Addr::new(192, 168, 0, 3);
+
+        let mut table = EcmpTable::new();
+        table.insert_cidr("10.0.0.0/8", vec![a, b, c]).unwrap();
+
+        assert_eq!(
+            table.lookup_failover("10.0.0.1", |_| true).unwrap(),
+            Some(&a)
+        );
+        assert_eq!(
+            table
+                .lookup_failover("10.0.0.1", |&next_hop| next_hop != a && next_hop != b)
+                .unwrap(),
+            Some(&c)
+        );
+        assert_eq!(
+            table
+                .lookup_failover("10.0.0.1", |&next_hop| next_hop != a)
+                .unwrap(),
+            Some(&b)
+        );
+        assert_eq!(table.lookup_failover("10.0.0.1", |_| false).unwrap(), None);
+        assert_eq!(table.lookup_failover("8.8.8.8", |_| true).unwrap(), None);
+    }
+
+    #[test]
+    fn test_lookup_weighted() {
+        let a = Ipv4Addr::new(192, 168, 0, 1);
+        let b = Ipv4Addr::new(192, 168, 0, 2);
+
+        let mut table = WeightedTable::new();
+        table
+            .insert_cidr("10.0.0.0/8", vec![(a, 1), (b, 3)])
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", vec![(a, 0), (b, 0)])
+            .unwrap();
+
+        let mut counts = [0; 2];
+        for flow in 0..10_000_u32 {
+            let flow_hash = flow.wrapping_mul(0x9e37_79b9);
+            match table.lookup_weighted("10.0.0.1", flow_hash).unwrap() {
+                Some(&next_hop) if next_hop == a => counts[0] += 1,
+                Some(&next_hop) if next_hop == b => counts[1] += 1,
+                other => panic!("unexpected next-hop {other:?}"),
+            }
+        }
+        let ratio = counts[1] as f64 / counts[0] as f64;
+        assert!((2.7..3.3).contains(&ratio), "{counts:?}");
+
+        let first = table.lookup_weighted("10.0.0.1", 0xdead_beef).unwrap();
+        for _ in 0..10 {
+            assert_eq!(
+                table.lookup_weighted("10.0.0.1", 0xdead_beef).unwrap(),
+                first
+            );
+        }
+
+        assert_eq!(table.lookup_weighted("10.0.1.1", 7).unwrap(), None);
+        assert_eq!(table.lookup_weighted("8.8.8.8", 7).unwrap(), None);
+    }
+
+    #[test]
+    fn test_lookup_trace() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+
+        let trace = table.lookup_trace("10.0.1.5").unwrap();
+        assert_eq!(
+            trace.terminals_seen,
+            vec![
+                (8, Ipv4Addr::new(192, 168, 0, 1)),
+                (24, Ipv4Addr::new(192, 168, 0, 2))
+            ]
+        );
+        assert_eq!(trace.bits_consumed, bits::<Ipv4Addr>(0x0A00_0100, 24));
+        assert_eq!(trace.result, Some(Ipv4Addr::new(192, 168, 0, 2)));
+
+        let trace = table.lookup_trace("10.0.2.1").unwrap();
+        assert_eq!(trace.terminals_seen.len(), 1);
+        assert_eq!(trace.bits_consumed.len(), 8);
+        assert_eq!(trace.result, Some(Ipv4Addr::new(192, 168, 0, 1)));
+
+        let trace = table.lookup_trace("8.8.8.8").unwrap();
+        assert_eq!(
+            trace,
+            LookupTrace {
+                bits_consumed: vec![],
+                terminals_seen: vec![],
+                result: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_with_default() {
+        let mut table = RouteTable::with_default(Ipv4Addr::new(192, 168, 0, 254));
+        assert!(table.is_empty());
+        assert_eq!(table.fallback(), Some(&Ipv4Addr::new(192, 168, 0, 254)));
+        assert_eq!(
+            table.lookup("8.8.8.8").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 254))
+        );
+
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup_required("1.1.1.1"),
+            Ok(Ipv4Addr::new(192, 168, 0, 254))
+        );
+        assert_eq!(table.lookup_detailed("1.1.1.1").unwrap(), None);
+        assert_eq!(table.routes().count(), 1);
+
+        // An installed default route wins over the fallback.
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        assert_eq!(
+            table.lookup("1.1.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+        table.remove_cidr("0.0.0.0/0").unwrap();
+        assert_eq!(
+            table.lookup("1.1.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 254))
+        );
+
+        let mut plain = create_table();
+        plain
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_ne!(plain, table);
+        assert_eq!(plain.fallback(), None);
+    }
+
+    #[test]
+    fn test_with_default_lookups() {
+        let fallback = Ipv4Addr::new(192, 168, 0, 254);
+        let mut table = RouteTable::with_default(fallback);
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        // Lookups that only return a destination fall back to the default.
+        assert_eq!(table.lookup_u32(0x0101_0101), Some(&fallback));
+        assert_eq!(table.lookup_bounded("1.1.1.1", 32).unwrap(), Some(fallback));
+        assert_eq!(table.lookup_bounded("10.0.0.1", 7).unwrap(), Some(fallback));
+        assert_eq!(
+            table.lookup_with_meta("1.1.1.1").unwrap(),
+            Some((fallback, None))
+        );
+        assert!(table.contains_ip("1.1.1.1").unwrap());
+        assert_eq!(
+            table.lookup_trace("1.1.1.1").unwrap().result,
+            Some(fallback)
+        );
+        assert_eq!(
+            table.lookup_str("1.1.1.1").unwrap(),
+            Some("192.168.0.254".to_owned())
+        );
+        assert_eq!(
+            table.lookup_u32(0x0A00_0001),
+            Some(&Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        // Those that report the matched prefix don't.
+        assert_eq!(table.lookup_detailed("1.1.1.1").unwrap(), None);
+        assert_eq!(
+            table.lookup_excluding_longer_than("1.1.1.1", 32).unwrap(),
+            None
+        );
+        assert_eq!(table.lookup_with_backup("1.1.1.1").unwrap(), (None, None));
+        assert_eq!(table.match_cidr("1.1.1.1").unwrap(), None);
+        assert!(table.lookup_all("1.1.1.1").unwrap().is_empty());
+
+        let mut table = EcmpTable::with_default(vec![fallback]);
+        table
+            .insert_cidr("10.0.0.0/8", vec![Ipv4Addr::new(192, 168, 0, 1)])
+            .unwrap();
+        assert_eq!(table.lookup_ecmp("1.1.1.1").unwrap(), Some(&[fallback][..]));
+        assert_eq!(table.lookup_first("1.1.1.1").unwrap(), Some(fallback));
+
+        let table = WeightedTable::with_default(vec![(fallback, 1)]);
+        assert_eq!(
+            table.lookup_weighted("1.1.1.1", 7).unwrap(),
+            Some(&fallback)
+        );
+    }
+
+    #[test]
+    fn test_lookup_required() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup_required("10.0.0.1"),
+            Ok(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup_required("8.8.8.8"),
+            Err(RotabError::NoRoute {
+                ip: Ipv4Addr::new(8, 8, 8, 8).into()
+            })
+        );
+        assert!(matches!(
+            table.lookup_required("10.0.1"),
+            Err(RotabError::InvalidAddr(_))
+        ));
+
+        let table = Table6::<u32>::new();
+        assert_eq!(
+            table.lookup_required("2001:db8::1"),
+            Err(RotabError::NoRoute {
+                ip: Ipv6Addr::from_str("2001:db8::1").unwrap().into()
+            })
+        );
+    }
+
+    #[test]
+    fn test_contains_ip() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.128/25", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert!(table.contains_ip("10.0.0.1").unwrap());
+        assert!(table.contains_ip(Ipv4Addr::new(10, 0, 0, 200)).unwrap());
+        assert!(!table.contains_ip("10.0.1.1").unwrap());
+        assert!(!table.contains_ip("8.8.8.8").unwrap());
+        assert!(table.contains_ip("10.0.1").is_err());
+
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        assert!(table.contains_ip("8.8.8.8").unwrap());
+    }
+
+    #[test]
+    fn test_lookup_all() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/16", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/24", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 4))
+            .unwrap();
+
+        let matches = table.lookup_all("10.0.0.1").unwrap();
+        assert_eq!(
+            matches,
+            vec![
+                Match {
+                    dest: Ipv4Addr::new(192, 168, 0, 1),
+                    prefix_len: 8,
+                    network: Ipv4Addr::new(10, 0, 0, 0),
+                },
+                Match {
+                    dest: Ipv4Addr::new(192, 168, 0, 2),
+                    prefix_len: 16,
+                    network: Ipv4Addr::new(10, 0, 0, 0),
+                },
+                Match {
+                    dest: Ipv4Addr::new(192, 168, 0, 3),
+                    prefix_len: 24,
+                    network: Ipv4Addr::new(10, 0, 0, 0),
+                },
+            ]
+        );
+
+        assert_eq!(table.lookup_all("10.1.0.1").unwrap().len(), 1);
+        assert!(table.lookup_all("11.0.0.1").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_default_and_clone() {
+        let mut table = RouteTable::default();
+        assert!(table.is_empty());
+
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        let snapshot = table.clone();
+
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(
+            snapshot.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(snapshot.lookup("10.0.1.1").unwrap(), None);
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+    }
+
+    #[test]
+    fn test_get_exact() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_eq!(table.get_exact("10.0.1.0", 24).unwrap(), None);
+        assert_eq!(
+            table.get_exact(Ipv4Addr::new(10, 0, 0, 0), 8).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert_eq!(
+            table.get_exact("10.0.1.0", 24).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            table.get_exact("10.0.1.77", 24).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(table.get_exact("10.0.0.0", 16).unwrap(), None);
+        assert_eq!(table.get_exact("10.0.1.0", 25).unwrap(), None);
+        assert_eq!(table.get_exact("0.0.0.0", 0).unwrap(), None);
+        assert_eq!(
+            table.get_exact("10.0.1.0", 33),
+            Err(RotabError::InvalidPrefixLen(33))
+        );
+        assert!(table.get_exact("10.0.1", 24).is_err());
+
+        let mut table = Table6::new();
+        table.insert_cidr("2001:db8::/32", 1).unwrap();
+        assert_eq!(table.get_exact("2001:db8::", 32).unwrap(), Some(1));
+        assert_eq!(table.get_exact("2001:db8::", 48).unwrap(), None);
+    }
+
+    #[test]
+    fn test_contains_exact() {
+        let mut table = create_table();
+        table
+            .insert_range("10.0.0.0", "10.255.255.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert!(table.contains_exact("10.0.0.0", "10.255.255.255").unwrap());
+        assert!(!table.contains_exact("10.0.0.0", "10.0.0.255").unwrap());
+        assert!(table.lookup("10.0.0.1").unwrap().is_some());
+
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert!(table.contains_exact("10.0.0.0", "10.0.0.255").unwrap());
+        assert!(!table.contains_exact("10.0.0.0", "10.0.0.127").unwrap());
+        assert!(!table.contains_exact("0.0.0.0", "255.255.255.255").unwrap());
+
+        assert_eq!(
+            table.contains_exact("10.0.0.1", "10.0.0.6"),
+            Err(RotabError::NotSingleCidr {
+                start: Ipv4Addr::new(10, 0, 0, 1).into(),
+                end: Ipv4Addr::new(10, 0, 0, 6).into(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut table = create_table();
+        table
+            .insert_range("0.0.0.0", "255.255.255.255", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_range("10.0.1.1", "10.0.1.6", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert!(table.lookup("10.0.0.1").unwrap().is_some());
+
+        table.clear();
+        assert!(table.is_empty());
+        assert_eq!(node_count(&table), 1);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), None);
+        assert_eq!(table.lookup("8.8.8.8").unwrap(), None);
+
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        assert_eq!(table.len(), 1);
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let mut table = RouteTable::with_capacity(10_000);
+        assert!(table.is_empty());
+        assert!(table.capacity() >= 10_000);
+
+        let capacity = table.capacity();
+        let arena = table.nodes.as_ptr();
+        for i in 0..10_000u32 {
+            table
+                .insert_u32_prefix(i.wrapping_mul(0x9E37_79B9), 32, Ipv4Addr::from_bits(i))
+                .unwrap();
+        }
+
+        assert_eq!(table.len(), 10_000);
+        assert_eq!(table.capacity(), capacity);
+        assert_eq!(table.nodes.as_ptr(), arena);
+        assert_eq!(
+            table
+                .lookup(Ipv4Addr::from_bits(42u32.wrapping_mul(0x9E37_79B9)))
+                .unwrap(),
+            Some(Ipv4Addr::from_bits(42))
+        );
+    }
+
+    #[test]
+    fn test_reserve() {
+        let mut table = create_table();
+        table.reserve(1_000);
+        assert!(table.node_capacity() > 2 * 1_000);
+
+        let node_capacity = table.node_capacity();
+        let arena = table.nodes.as_ptr();
+        for i in 0..1_000u32 {
+            table
+                .insert_u32_prefix(i.wrapping_mul(0x9E37_79B9), 32, Ipv4Addr::from_bits(i))
+                .unwrap();
+        }
+        assert_eq!(table.node_capacity(), node_capacity);
+        assert_eq!(table.nodes.as_ptr(), arena);
+
+        // Freed slots count towards the reservation.
+        table.remove_range("0.0.0.0", "127.255.255.255").unwrap();
+        let free = table.free.len();
+        table.reserve(free / 2);
+        assert_eq!(table.node_capacity(), node_capacity);
+        table.reserve(1_000);
+        assert!(table.node_capacity() >= table.nodes.len() + 2 * 1_000 - free);
+    }
+
+    #[test]
+    fn test_try_from_iter() {
+        let routes = vec![
+            (
+                "0.0.0.0".to_owned(),
+                "255.255.255.255".to_owned(),
+                "default".to_owned(),
+            ),
+            (
+                "10.0.0.0".to_owned(),
+                "10.0.0.255".to_owned(),
+                "eth0".to_owned(),
+            ),
+            (
+                "10.0.1.0".to_owned(),
+                "10.0.1.255".to_owned(),
+                "eth1".to_owned(),
+            ),
+        ];
+
+        let mut table: Table<String> = Table::try_from_iter(routes).unwrap();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some("eth0".to_owned()));
+        assert_eq!(table.lookup("10.0.1.1").unwrap(), Some("eth1".to_owned()));
+        assert_eq!(table.lookup("8.8.8.8").unwrap(), Some("default".to_owned()));
+
+        table
+            .try_extend([("10.0.2.0", "10.0.2.255", "eth2".to_owned())])
+            .unwrap();
+        assert_eq!(table.lookup("10.0.2.1").unwrap(), Some("eth2".to_owned()));
+    }
+
+    #[test]
+    fn test_load_from_iter_with_progress() {
+        let routes = || {
+            (0..10_000_u32).map(|i| {
+                let ip = Ipv4Addr::from_bits(0x0A00_0000 + i);
+                (ip, ip, i)
+            })
+        };
+
+        let mut table = Table::<u32>::new();
+        let mut calls = vec![];
+        let loaded = table
+            .load_from_iter_with_progress(routes(), 1_000, |loaded| calls.push(loaded))
+            .unwrap();
+        assert_eq!(loaded, 10_000);
+        assert_eq!(calls, (1..=10).map(|i| i * 1_000).collect::<Vec<_>>());
+        assert_eq!(table.len(), 10_000);
+        assert_eq!(table.lookup("10.0.39.15").unwrap(), Some(9_999));
+        assert_eq!(table.lookup("10.0.39.16").unwrap(), None);
+
+        let mut table = Table::<u32>::new();
+        let mut calls = 0;
+        table
+            .load_from_iter_with_progress(routes(), 3_000, |_| calls += 1)
+            .unwrap();
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        table
+            .load_from_iter_with_progress(routes(), 0, |_| calls += 1)
+            .unwrap();
+        assert_eq!(calls, 0);
+
+        let mut table = Table::<u32>::new();
+        let invalid = [("10.0.0.0", "10.0.0.0", 0), ("10.0.1", "10.0.1.0", 1)];
+        assert!(matches!(
+            table.load_from_iter_with_progress(invalid, 1, |_| ()),
+            Err(RotabError::InvalidAddr(_))
+        ));
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_insert_many() {
+        let routes = [
+            ("10.0.0.0", "10.0.0.255", "eth0"),
+            ("10.0.1", "10.0.1.255", "eth1"),
+            ("10.0.2.0", "10.0.2.255", "eth2"),
+            ("10.0.3.255", "10.0.3.0", "eth3"),
+            ("0.0.0.0", "255.255.255.255", "default"),
+        ]
+        .map(|(start, end, dest)| (start.to_owned(), end.to_owned(), dest.to_owned()));
+
+        let mut table = Table::<String>::new();
+        let errors = table.insert_many(routes);
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], (1, RotabError::InvalidAddr(_))));
+        assert_eq!(
+            errors[1],
+            (
+                3,
+                RotabError::InvertedRange {
+                    start: Ipv4Addr::new(10, 0, 3, 255).into(),
+                    end: Ipv4Addr::new(10, 0, 3, 0).into(),
+                }
+            )
+        );
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some("eth0".to_owned()));
+        assert_eq!(table.lookup("10.0.2.1").unwrap(), Some("eth2".to_owned()));
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some("default".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_try_from_iter_invalid() {
+        let routes = [
+            ("10.0.0.0", "10.0.0.255", 0),
+            ("10.0.1", "10.0.1.255", 1),
+            ("10.0.2.0", "10.0.2.255", 2),
+        ];
+        assert!(matches!(
+            Table::<u32>::try_from_iter(routes),
+            Err(RotabError::InvalidAddr(_))
+        ));
+
+        let mut table = Table::<u32>::new();
+        assert!(table.try_extend(routes).is_err());
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.lookup("10.0.2.1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_prefix_len_histogram() {
+        let mut table = Table::<u32>::new();
+        assert_eq!(table.prefix_len_histogram(), vec![0; 33]);
+
+        for cidr in [
+            "0.0.0.0/0",
+            "10.0.0.0/8",
+            "10.0.1.0/24",
+            "10.0.2.0/24",
+            "10.0.3.0/24",
+            "10.0.1.1/32",
+            "192.168.0.1/32",
+        ] {
+            table.insert_cidr(cidr, 0).unwrap();
+        }
+
+        let histogram = table.prefix_len_histogram();
+        assert_eq!(histogram.len(), 33);
+        assert_eq!(histogram[0], 1);
+        assert_eq!(histogram[8], 1);
+        assert_eq!(histogram[24], 3);
+        assert_eq!(histogram[32], 2);
+        assert_eq!(histogram.iter().sum::<usize>(), table.len());
+
+        let mut table = Table6::<u32>::new();
+        table.insert_cidr("2001:db8::/32", 0).unwrap();
+        table.insert_cidr("2001:db8::1/128", 0).unwrap();
+        let histogram = table.prefix_len_histogram();
+        assert_eq!(histogram.len(), 129);
+        assert_eq!((histogram[32], histogram[128]), (1, 1));
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut table = create_table();
+        let stats = table.stats();
+        assert_eq!(stats.node_count, 1);
+        assert_eq!(stats.route_count, 0);
+        assert_eq!(stats.max_depth, 0);
+
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        table
+            .insert_cidr("192.168.0.0/16", Ipv4Addr::new(192, 168, 0, 4))
+            .unwrap();
+
+        // root -> 10/8 -> 10.0.0/23 -> {10.0.0/24, 10.0.1/24}, root -> 192.168/16
+        let stats = table.stats();
+        assert_eq!(stats.node_count, 6);
+        assert_eq!(stats.route_count, 4);
+        assert_eq!(stats.max_depth, 3);
+        assert!(stats.estimated_bytes >= 6 * size_of::<Node<Ipv4Addr, u32>>());
+
+        table.remove_range("10.0.0.0", "10.0.0.255").unwrap();
+        let stats = table.stats();
+        assert_eq!(stats.node_count, 4);
+        assert_eq!(stats.max_depth, 2);
+    }
+
+    #[test]
+    fn test_match_cidr() {
+        let mut table = create_table();
+        assert_eq!(table.match_cidr("10.0.1.1").unwrap(), None);
+
+        table
+            .insert_range("0.0.0.0", "255.255.255.255", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_range("10.0.0.0", "10.255.255.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(
+            table.match_cidr("10.0.1.1").unwrap(),
+            Some(("10.0.1.0/24".to_owned(), Ipv4Addr::new(192, 168, 0, 2)))
+        );
+        assert_eq!(
+            table.match_cidr("10.0.2.1").unwrap(),
+            Some(("10.0.0.0/8".to_owned(), Ipv4Addr::new(192, 168, 0, 1)))
+        );
+        assert_eq!(
+            table.match_cidr("8.8.8.8").unwrap(),
+            Some(("0.0.0.0/0".to_owned(), Ipv4Addr::new(192, 168, 0, 0)))
+        );
+
+        let mut table6 = Table6::new();
+        table6.insert_cidr("2001:db8::/32", 1).unwrap();
+        assert_eq!(
+            table6.match_cidr("2001:db8::1").unwrap(),
+            Some(("2001:db8::/32".to_owned(), 1))
+        );
+    }
+
+    fn create_merge_tables() -> (RouteTable, RouteTable) {
+        let mut dynamic = create_table();
+        dynamic
+            .insert_cidr("10.0.0.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        dynamic
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        let mut fixed = create_table();
+        fixed
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(172, 16, 0, 2))
+            .unwrap();
+        fixed
+            .insert_cidr("10.0.2.0/24", Ipv4Addr::new(172, 16, 0, 3))
+            .unwrap();
+
+        (dynamic, fixed)
+    }
+
+    #[test]
+    fn test_merge_keep_existing() {
+        let (mut table, other) = create_merge_tables();
+        table.merge(&other, MergePolicy::KeepExisting).unwrap();
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(Ipv4Addr::new(172, 16, 0, 3))
+        );
+    }
+
+    #[test]
+    fn test_merge_overwrite() {
+        let (mut table, other) = create_merge_tables();
+        table.merge(&other, MergePolicy::Overwrite).unwrap();
+
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(172, 16, 0, 2))
+        );
+        assert_eq!(
+            table.lookup("10.0.2.1").unwrap(),
+            Some(Ipv4Addr::new(172, 16, 0, 3))
+        );
+    }
+
+    #[test]
+    fn test_merge_error() {
+        let (mut table, other) = create_merge_tables();
+        assert_eq!(
+            table.merge(&other, MergePolicy::Error),
+            Err(RotabError::MergeConflict(vec![(
+                Ipv4Addr::new(10, 0, 1, 0).into(),
+                24
+            )]))
+        );
+
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.lookup("10.0.2.1").unwrap(), None);
+
+        table.remove_range("10.0.1.0", "10.0.1.255").unwrap();
+        table.merge(&other, MergePolicy::Error).unwrap();
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn test_merge_keeps_meta() {
+        let meta = |admin_distance, preferred| RouteMeta {
+            admin_distance,
+            preferred,
+            ..RouteMeta::default()
+        };
+        let mut table = Table::<u32>::new();
+        table
+            .insert_range_with_meta("10.0.0.0", "10.0.0.255", 1, meta(20, false))
+            .unwrap();
+
+        let mut other = Table::<u32>::new();
+        other
+            .insert_range_with_meta("10.0.0.0", "10.0.0.255", 2, meta(110, false))
+            .unwrap();
+        other
+            .insert_range_with_meta("10.1.0.0", "10.1.255.255", 3, meta(110, true))
+            .unwrap();
+        other.insert_cidr("10.1.1.0/24", 4).unwrap();
+
+        table.merge(&other, MergePolicy::Overwrite).unwrap();
+        // The lower admin distance wins over the overwrite.
+        assert_eq!(
+            table.lookup_with_meta("10.0.0.1").unwrap(),
+            Some((1, Some(meta(20, false))))
+        );
+        // The preferred flag came along, so the /16 beats the /24.
+        assert_eq!(
+            table.lookup_with_meta("10.1.1.1").unwrap(),
+            Some((3, Some(meta(110, true))))
+        );
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn test_remove_cidr() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert!(table.remove_cidr("10.0.1.7/24").unwrap());
+        assert!(!table.remove_cidr("10.0.1.0/24").unwrap());
+        assert!(!table.remove_cidr("10.0.0.0/16").unwrap());
+        assert!(!table.contains_exact("10.0.1.0", "10.0.1.255").unwrap());
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.len(), 1);
+        assert_eq!(node_count(&table), 2);
+
+        assert!(matches!(
+            table.remove_cidr("10.0.0.0/33"),
+            Err(RotabError::InvalidPrefixLen(33))
+        ));
+    }
+
+    #[test]
+    fn test_preferred_route() {
+        let preferred = RouteMeta {
+            preferred: true,
+            ..RouteMeta::default()
+        };
+
+        let mut table = create_table();
+        table
+            .insert_range_with_meta(
+                "10.0.0.0",
+                "10.0.255.255",
+                Ipv4Addr::new(192, 168, 0, 1),
+                preferred,
+            )
+            .unwrap();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_range("10.1.0.0", "10.1.0.255", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table
+                .lookup_detailed("10.0.1.1")
+                .unwrap()
+                .map(|m| m.prefix_len),
+            Some(16)
+        );
+        assert_eq!(
+            table.lookup_bounded("10.0.1.1", 24).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.1.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+
+        // The longest preferred route wins among several.
+        table
+            .insert_range_with_meta(
+                "10.0.1.128",
+                "10.0.1.255",
+                Ipv4Addr::new(192, 168, 0, 4),
+                preferred,
+            )
+            .unwrap();
+        table
+            .insert_range("10.0.1.129", "10.0.1.129", Ipv4Addr::new(192, 168, 0, 5))
+            .unwrap();
+        assert_eq!(
+            table.lookup("10.0.1.129").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 4))
+        );
+
+        // Replacing the route without metadata drops the preference.
+        table
+            .insert_range("10.0.0.0", "10.0.255.255", Ipv4Addr::new(192, 168, 0, 6))
+            .unwrap();
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+    }
+
+    #[test]
+    fn test_lookup_with_meta() {
+        let bgp = RouteMeta {
+            source: RouteSource::Bgp,
+            admin_distance: 20,
+            timestamp: 1_700_000_000,
+            preferred: false,
+        };
+        let ospf = RouteMeta {
+            source: RouteSource::Ospf,
+            admin_distance: 110,
+            timestamp: 1_700_000_100,
+            preferred: false,
+        };
+
+        let mut table = create_table();
+        table
+            .insert_range_with_meta(
+                "10.0.0.0",
+                "10.255.255.255",
+                Ipv4Addr::new(192, 168, 0, 1),
+                bgp,
+            )
+            .unwrap();
+        table
+            .insert_range_with_meta(
+                "10.0.1.0",
+                "10.0.1.255",
+                Ipv4Addr::new(192, 168, 0, 2),
+                ospf,
+            )
+            .unwrap();
+        table
+            .insert_range("172.16.0.0", "172.16.0.255", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup_with_meta("10.0.1.1").unwrap(),
+            Some((Ipv4Addr::new(192, 168, 0, 2), Some(ospf)))
+        );
+        assert_eq!(
+            table.lookup_with_meta("10.0.2.1").unwrap(),
+            Some((Ipv4Addr::new(192, 168, 0, 1), Some(bgp)))
+        );
+        assert_eq!(
+            table.lookup_with_meta("172.16.0.1").unwrap(),
+            Some((Ipv4Addr::new(192, 168, 0, 3), None))
+        );
+        assert_eq!(table.lookup_with_meta("8.8.8.8").unwrap(), None);
+
+        table.remove_range("10.0.1.0", "10.0.1.255").unwrap();
+        assert_eq!(
+            table.lookup_with_meta("10.0.1.1").unwrap(),
+            Some((Ipv4Addr::new(192, 168, 0, 1), Some(bgp)))
+        );
+    }
+
+    #[test]
+    fn test_admin_distance_preference() {
+        let meta = |admin_distance| RouteMeta {
+            admin_distance,
+            ..RouteMeta::default()
+        };
+        let ebgp = Ipv4Addr::new(192, 168, 0, 20);
+        let ospf = Ipv4Addr::new(192, 168, 0, 110);
+
+        let mut table = create_table();
+        table
+            .insert_range_with_meta("10.0.0.0", "10.0.0.255", ebgp, meta(20))
+            .unwrap();
+        assert_eq!(
+            table.insert_range_with_meta("10.0.0.0", "10.0.0.255", ospf, meta(110)),
+            Err(RotabError::LowerDistanceInstalled {
+                network: Ipv4Addr::new(10, 0, 0, 0).into(),
+                prefix_len: 24,
+                admin_distance: 20,
+            })
+        );
+        assert_eq!(
+            table.lookup_with_meta("10.0.0.1").unwrap(),
+            Some((ebgp, Some(meta(20))))
+        );
+
+        // A range with one such prefix inserts none of the others.
+        assert!(
+            table
+                .insert_range_with_meta("9.255.255.0", "10.0.0.255", ospf, meta(110))
+                .is_err()
+        );
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.lookup("9.255.255.1").unwrap(), None);
+
+        let mut table = create_table();
+        table
+            .insert_range_with_meta("10.0.0.0", "10.0.0.255", ospf, meta(110))
+            .unwrap();
+        assert_eq!(
+            table
+                .insert_range_with_meta("10.0.0.0", "10.0.0.255", ebgp, meta(20))
+                .unwrap(),
+            Some(ospf)
+        );
+        assert_eq!(
+            table.lookup_with_meta("10.0.0.1").unwrap(),
+            Some((ebgp, Some(meta(20))))
+        );
+        assert_eq!(table.len(), 1);
+
+        // An equal distance replaces the route, as does a longer prefix
+        // regardless of distance.
+        let replacement = Ipv4Addr::new(192, 168, 0, 21);
+        table
+            .insert_range_with_meta("10.0.0.0", "10.0.0.255", replacement, meta(20))
+            .unwrap();
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(replacement));
+
+        table
+            .insert_range_with_meta("10.0.0.0", "10.0.0.127", ospf, meta(110))
+            .unwrap();
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(ospf));
+        assert_eq!(table.lookup("10.0.0.200").unwrap(), Some(replacement));
+
+        // An insert without metadata has no distance to compare, so it
+        // replaces the route and its metadata.
+        let mut table = Table::<u32>::new();
+        table
+            .insert_range_with_meta("10.0.0.0", "10.0.0.255", 1, meta(1))
+            .unwrap();
+        assert_eq!(
+            table.insert_range("10.0.0.0", "10.0.0.255", 3).unwrap(),
+            Some(1)
+        );
+        assert_eq!(table.lookup_with_meta("10.0.0.1").unwrap(), Some((3, None)));
+    }
+
+    #[test]
+    fn test_lookup_many() {
+        let mut table = create_table();
+        table
+            .insert_range("0.0.0.0", "255.255.255.255", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_range("10.0.1.0", "10.0.1.255", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        let ips = [
+            Ipv4Addr::new(10, 0, 1, 1),
+            Ipv4Addr::new(8, 8, 8, 8),
+            Ipv4Addr::new(10, 0, 0, 1),
+            Ipv4Addr::new(10, 0, 1, 255),
+        ];
+        let results = table
+            .lookup_many(ips)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            results,
+            ips.iter()
+                .map(|&ip| table.lookup(ip).unwrap())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            results,
+            vec![
+                Some(Ipv4Addr::new(192, 168, 0, 2)),
+                Some(Ipv4Addr::new(192, 168, 0, 0)),
+                Some(Ipv4Addr::new(192, 168, 0, 1)),
+                Some(Ipv4Addr::new(192, 168, 0, 2)),
+            ]
+        );
+
+        let mut results = table.lookup_many(["10.0.0.1", "10.0.0", "10.0.1.1"]);
+        assert!(results.next().unwrap().is_ok());
+        assert!(matches!(
+            results.next(),
+            Some(Err(RotabError::InvalidAddr(_)))
+        ));
+        assert_eq!(
+            results.next().unwrap().unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert!(results.next().is_none());
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/25", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.128/25", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/25", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(table.aggregate(), 1);
+        assert_eq!(table.len(), 3);
+        assert!(table.contains_exact("10.0.0.0", "10.0.0.255").unwrap());
+        assert!(!table.contains_exact("10.0.0.0", "10.0.0.127").unwrap());
+        assert_eq!(
+            table.lookup("10.0.0.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.0.1.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+
+        assert_eq!(table.aggregate(), 0);
+    }
+
+    #[test]
+    fn test_auto_aggregate() {
+        let mut table = RouteTable::new_auto_aggregate();
+        table
+            .insert_cidr("10.0.0.0/25", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.128/25", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_eq!(table.len(), 1);
+        assert!(table.contains_exact("10.0.0.0", "10.0.0.127").unwrap());
+        assert!(!table.contains_exact("10.0.0.0", "10.0.0.255").unwrap());
+
+        // Different destinations stay apart.
+        table
+            .insert_cidr("10.0.1.0/25", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert_eq!(table.len(), 3);
+
+        // Fixing the destination merges the /25s, and then the /24s.
+        table
+            .insert_cidr("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_eq!(
+            table
+                .routes()
+                .map(|(n, l, &d)| (n, l, d))
+                .collect::<Vec<_>>(),
+            vec![(
+                Ipv4Addr::new(10, 0, 0, 0),
+                23,
+                Ipv4Addr::new(192, 168, 0, 1)
+            )]
+        );
+        assert_eq!(
+            table.lookup("10.0.1.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        let mut table = Table::<u32>::new_auto_aggregate();
+        for i in 0..256 {
+            table.insert_cidr(&format!("10.0.0.{i}/32"), 7).unwrap();
+        }
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.max_prefix_len(), 24);
+
+        let mut table = Table::<u32>::new();
+        table.insert_cidr("10.0.0.0/25", 1).unwrap();
+        table.insert_cidr("10.0.0.128/25", 1).unwrap();
+        assert_eq!(table.len(), 2);
+    }
+
+    #[test]
+    fn test_prune_redundant() {
+        let mut table = create_table();
+        let cidrs = [
+            ("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.1.0.0/16", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.1.1.0/24", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.2.0.0/16", Ipv4Addr::new(192, 168, 0, 2)),
+            ("10.2.1.0/24", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.2.1.1/32", Ipv4Addr::new(192, 168, 0, 1)),
+            ("172.16.0.0/12", Ipv4Addr::new(192, 168, 0, 1)),
+        ];
+        for (cidr, dest) in cidrs {
+            table.insert_cidr(cidr, dest).unwrap();
+        }
+        table
+            .insert_range_with_meta(
+                "10.3.0.0",
+                "10.3.255.255",
+                Ipv4Addr::new(192, 168, 0, 1),
+                RouteMeta {
+                    admin_distance: 20,
+                    ..RouteMeta::default()
+                },
+            )
+            .unwrap();
+        let before = table.clone();
+
+        assert_eq!(table.prune_redundant(), 3);
+        assert_eq!(
+            table
+                .routes()
+                .map(|(network, prefix_len, _)| (network, prefix_len))
+                .collect::<Vec<_>>(),
+            vec![
+                (Ipv4Addr::new(10, 0, 0, 0), 8),
+                (Ipv4Addr::new(10, 2, 0, 0), 16),
+                (Ipv4Addr::new(10, 2, 1, 0), 24),
+                (Ipv4Addr::new(10, 3, 0, 0), 16),
+                (Ipv4Addr::new(172, 16, 0, 0), 12),
+            ]
+        );
+        assert!(before.coverage_diff(&table).is_empty());
+        assert_eq!(table.prune_redundant(), 0);
+    }
+
+    #[test]
+    fn test_aggregate_keeps_parent_route() {
+        let mut table = Table::<u32>::new();
+        table.insert_cidr("10.0.0.0/24", 1).unwrap();
+        table.insert_cidr("10.0.0.0/25", 2).unwrap();
+        table.insert_cidr("10.0.0.128/25", 2).unwrap();
+
+        // Merging would replace the /24's own route.
+        assert_eq!(table.aggregate(), 0);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.get_exact("10.0.0.0", 24).unwrap(), Some(1));
+        assert_eq!(table.lookup("10.0.0.200").unwrap(), Some(2));
+
+        table.insert_cidr("10.0.0.0/24", 2).unwrap();
+        assert_eq!(table.aggregate(), 1);
+        assert_eq!(
+            table
+                .routes()
+                .map(|(n, l, &d)| (n, l, d))
+                .collect::<Vec<_>>(),
+            vec![(Ipv4Addr::new(10, 0, 0, 0), 24, 2)]
+        );
+    }
+
+    #[test]
+    fn test_aggregate_cascades() {
+        let mut table = Table::<u32>::new();
+        table.insert_range("10.0.0.0", "10.0.3.255", 1).unwrap();
+        table.insert_cidr("10.0.0.0/30", 2).unwrap();
+        for i in 0..4 {
+            table.insert_cidr(&format!("10.0.{i}.0/25"), 1).unwrap();
+            table.insert_cidr(&format!("10.0.{i}.128/25"), 1).unwrap();
+        }
+        assert_eq!(table.len(), 10);
+
+        // Four /25 pairs into /24s, then /24 pairs into the /23s and the /23
+        // pair into the /22 that was already installed.
+        assert_eq!(table.aggregate(), 7);
+        assert_eq!(
+            table
+                .routes()
+                .map(|(n, l, &d)| (n, l, d))
+                .collect::<Vec<_>>(),
+            vec![
+                (Ipv4Addr::new(10, 0, 0, 0), 22, 1),
+                (Ipv4Addr::new(10, 0, 0, 0), 30, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_insert_cidr_range_strict() {
+        let mut table = create_table();
+        table
+            .insert_cidr_range_strict("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert!(table.contains_exact("10.0.0.0", "10.0.0.255").unwrap());
+        assert_eq!(
+            table.lookup("10.0.0.7").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        assert_eq!(
+            table.insert_cidr_range_strict("10.0.1.0", "10.0.1.5", Ipv4Addr::new(192, 168, 0, 2)),
+            Err(RotabError::NotSingleCidr {
+                start: Ipv4Addr::new(10, 0, 1, 0).into(),
+                end: Ipv4Addr::new(10, 0, 1, 5).into(),
+            })
+        );
+        // Right size, but not aligned to its start.
+        assert!(matches!(
+            table.insert_cidr_range_strict("10.0.1.2", "10.0.1.5", Ipv4Addr::new(192, 168, 0, 2)),
+            Err(RotabError::NotSingleCidr { .. })
+        ));
+        assert_eq!(table.len(), 1);
+        assert_eq!(table.lookup("10.0.1.1").unwrap(), None);
+    }
+
+    #[test]
+    fn test_try_insert_disjoint() {
+        let mut table = create_table();
+        table
+            .try_insert_disjoint("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .try_insert_disjoint("10.0.2.0", "10.0.2.255", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert_eq!(table.len(), 2);
+
+        let before = table.clone();
+        assert_eq!(
+            table.try_insert_disjoint("10.0.0.0", "10.0.255.255", Ipv4Addr::new(192, 168, 0, 3)),
+            Err(RotabError::Overlaps {
+                conflicting: vec![
+                    (Ipv4Addr::new(10, 0, 0, 0).into(), 24),
+                    (Ipv4Addr::new(10, 0, 2, 0).into(), 24),
+                ],
+            })
+        );
+        assert_eq!(
+            table.try_insert_disjoint("10.0.0.128", "10.0.0.128", Ipv4Addr::new(192, 168, 0, 3)),
+            Err(RotabError::Overlaps {
+                conflicting: vec![(Ipv4Addr::new(10, 0, 0, 0).into(), 24)],
+            })
+        );
+        assert!(matches!(
+            table.try_insert_disjoint("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 3)),
+            Err(RotabError::Overlaps { .. })
+        ));
+        // Split into several blocks, two of which fall under the /24.
+        assert_eq!(
+            table.try_insert_disjoint("10.0.0.200", "10.0.1.9", Ipv4Addr::new(192, 168, 0, 3)),
+            Err(RotabError::Overlaps {
+                conflicting: vec![(Ipv4Addr::new(10, 0, 0, 0).into(), 24)],
+            })
+        );
+        assert_eq!(table, before);
+
+        // Adjacent, but disjoint, and not a single prefix.
+        table
+            .try_insert_disjoint("10.0.1.0", "10.0.1.99", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        assert_eq!(
+            table.lookup("10.0.1.50").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+        assert_eq!(table.lookup("10.0.1.100").unwrap(), None);
+    }
+
+    #[test]
+    fn test_split_prefix() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/23", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        assert_eq!(
+            table.split_prefix("10.0.0.0", "10.0.1.255", 24).unwrap(),
+            vec![
+                (Ipv4Addr::new(10, 0, 0, 0), 24),
+                (Ipv4Addr::new(10, 0, 1, 0), 24)
+            ]
+        );
+        assert!(!table.contains_exact("10.0.0.0", "10.0.1.255").unwrap());
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        // An existing route for a sub-prefix is kept.
+        table
+            .insert_cidr("10.0.1.128/26", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        assert_eq!(
+            table.split_prefix("10.0.1.0", "10.0.1.255", 26).unwrap(),
+            vec![
+                (Ipv4Addr::new(10, 0, 1, 0), 26),
+                (Ipv4Addr::new(10, 0, 1, 64), 26),
+                (Ipv4Addr::new(10, 0, 1, 192), 26)
+            ]
+        );
+        assert_eq!(
+            table.lookup("10.0.1.130").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            table.lookup("10.0.1.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        let mut table = Table::<u32>::new();
+        table.insert_cidr("255.255.255.252/30", 1).unwrap();
+        assert_eq!(
+            table
+                .split_prefix("255.255.255.252", "255.255.255.255", 32)
+                .unwrap()
+                .len(),
+            4
+        );
+        assert_eq!(table.lookup(Ipv4Addr::BROADCAST).unwrap(), Some(1));
+
+        assert_eq!(
+            table.split_prefix("10.0.0.0", "10.0.1.255", 24).unwrap(),
+            vec![]
+        );
+        assert_eq!(
+            table.split_prefix("255.255.255.252", "255.255.255.253", 31),
+            Err(RotabError::InvalidPrefixLen(31))
+        );
+        assert_eq!(
+            table.split_prefix("10.0.0.0", "10.0.0.255", 33),
+            Err(RotabError::InvalidPrefixLen(33))
+        );
+    }
+
+    #[test]
+    fn test_insert_cidr_strict() {
+        let mut table = create_table();
+        assert_eq!(
+            table.insert_cidr_strict("10.0.0.5/24", Ipv4Addr::new(192, 168, 0, 1)),
+            Err(RotabError::InvalidCidr("10.0.0.5/24".to_owned()))
+        );
+        assert!(table.is_empty());
+
+        table
+            .insert_cidr_strict("10.0.0.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr_strict("10.0.1.1/32", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr_strict("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        assert_eq!(table.len(), 3);
+        assert!(matches!(
+            table.insert_cidr_strict("10.0.0.0/33", Ipv4Addr::new(192, 168, 0, 4)),
+            Err(RotabError::InvalidPrefixLen(33))
+        ));
+
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.5/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert!(table.contains_exact("10.0.0.0", "10.0.0.255").unwrap());
+        assert_eq!(
+            table.lookup("10.0.0.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let mut table = create_table();
+        assert_eq!(table.to_string(), "");
+
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("172.16.0.0/12", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+
+        assert_eq!(
+            table.to_string(),
+            concat!(
+                "0.0.0.0/0 -> 192.168.0.0\n",
+                "10.0.0.0/8 -> 192.168.0.1\n",
+                "10.0.1.0/24 -> 192.168.0.2\n",
+                "172.16.0.0/12 -> 192.168.0.3\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_default_route_only_terminal() {
+        let mut table = create_table();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        assert_eq!(
+            table.lookup("200.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+
+        // The walk leaves the root on the first bit, which has no edge for
+        // 200.0.0.1, so only the root matches.
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        let m = table.lookup_detailed("200.0.0.1").unwrap().unwrap();
+        assert_eq!(m.prefix_len, 0);
+        assert_eq!(m.dest, Ipv4Addr::new(192, 168, 0, 0));
+
+        // 11.0.0.1 follows the edge to 10.0.0.0/8 but doesn't match it.
+        assert_eq!(
+            table.lookup("11.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+        assert_eq!(
+            table.lookup_all("11.0.0.1").unwrap(),
+            vec![Match {
+                dest: Ipv4Addr::new(192, 168, 0, 0),
+                prefix_len: 0,
+                network: Ipv4Addr::new(0, 0, 0, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut table = Table::<u32>::new();
+        for i in 0..1000_u32 {
+            let network = Ipv4Addr::from_bits(0x0A00_0000 + (i << 8));
+            table
+                .insert_range(network, Ipv4Addr::from_bits(network.to_bits() + 255), i)
+                .unwrap();
+        }
+        for i in 100..1000_u32 {
+            let network = Ipv4Addr::from_bits(0x0A00_0000 + (i << 8));
+            table
+                .remove_range(network, Ipv4Addr::from_bits(network.to_bits() + 255))
+                .unwrap();
+        }
+
+        let slots = table.nodes.len();
+        let live = node_count(&table);
+        assert_eq!(table.compact(), slots - live);
+        assert_eq!(table.nodes.len(), live);
+        assert!(table.free.is_empty());
+        assert!(table.nodes.len() < slots / 4);
+        assert_eq!(table.compact(), 0);
+
+        assert_eq!(table.len(), 100);
+        for i in 0..1000_u32 {
+            let expected = (i < 100).then_some(i);
+            let ip = Ipv4Addr::from_bits(0x0A00_0000 + (i << 8) + 1);
+            assert_eq!(table.lookup(ip).unwrap(), expected);
+        }
+
+        table.insert_cidr("10.0.200.0/24", 200).unwrap();
+        assert_eq!(table.lookup("10.0.200.1").unwrap(), Some(200));
+    }
+
+    #[test]
+    fn test_insert_range_exclusive() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+
+        // 10.0.0.0/8 minus 10.0.1.0/24 takes 16 prefixes: 10.0.0.0/24,
+        // 10.0.2.0/23, 10.0.4.0/22, ... 10.128.0.0/9.
+        assert_eq!(
+            table
+                .insert_range_exclusive("10.0.0.0", "10.255.255.255", Ipv4Addr::new(192, 168, 0, 8))
+                .unwrap(),
+            16
+        );
+        assert!(!table.contains_exact("10.0.0.0", "10.255.255.255").unwrap());
+
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(
+            table.lookup("10.0.1.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        for ip in [
+            "10.0.0.1",
+            "10.0.2.1",
+            "10.0.255.1",
+            "10.200.0.1",
+            "10.255.255.255",
+        ] {
+            assert_eq!(
+                table.lookup(ip).unwrap(),
+                Some(Ipv4Addr::new(192, 168, 0, 8))
+            );
+        }
+        assert_eq!(
+            table.lookup("11.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+
+        // Everything in the range is handled now.
+        assert_eq!(
+            table
+                .insert_range_exclusive("10.0.0.0", "10.255.255.255", Ipv4Addr::new(192, 168, 0, 9))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_top_of_address_space() {
+        let mut table = create_table();
+        table
+            .insert_cidr("255.255.255.255/32", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        assert_eq!(
+            table.lookup(Ipv4Addr::BROADCAST).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(table.lookup("255.255.255.254").unwrap(), None);
+
+        table
+            .insert_range(
+                "255.255.255.0",
+                "255.255.255.255",
+                Ipv4Addr::new(192, 168, 0, 2),
+            )
+            .unwrap();
+        assert_eq!(
+            table.lookup("255.255.255.254").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            table.lookup(Ipv4Addr::BROADCAST).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        // Not aligned, so it splits into blocks up to the last address.
+        table
+            .insert_range(
+                "255.255.254.1",
+                "255.255.255.255",
+                Ipv4Addr::new(192, 168, 0, 3),
+            )
+            .unwrap();
+        assert_eq!(table.len(), 10);
+        assert_eq!(
+            table.lookup("255.255.254.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+        assert_eq!(table.lookup("255.255.254.0").unwrap(), None);
+        assert_eq!(
+            table.lookup(Ipv4Addr::BROADCAST).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+
+        assert!(
+            table
+                .remove_range("255.255.255.255", "255.255.255.255")
+                .unwrap()
+        );
+        assert_eq!(
+            table.lookup(Ipv4Addr::BROADCAST).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+    }
+
+    #[test]
+    fn test_insert_range_exclusive_edges() {
+        let mut table = Table::<u32>::new();
+        table.insert_cidr("0.0.0.0/1", 1).unwrap();
+        table.insert_cidr("255.255.255.255/32", 2).unwrap();
+
+        assert_eq!(
+            table
+                .insert_range_exclusive("0.0.0.0", "255.255.255.255", 3)
+                .unwrap(),
+            31
+        );
+        assert_eq!(table.lookup("10.0.0.1").unwrap(), Some(1));
+        assert_eq!(table.lookup("200.0.0.1").unwrap(), Some(3));
+        assert_eq!(table.lookup("255.255.255.254").unwrap(), Some(3));
+        assert_eq!(table.lookup("255.255.255.255").unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_load_from_str() {
+        let input = "
+            # static routes
+            0.0.0.0/0 192.168.0.0
+
+            10.0.0.0/8\t192.168.0.1
+            10.0.1.0/24, 192.168.0.2
+              # the /25 is more specific
+            10.0.1.128/25 -> 192.168.0.3
+        ";
+
+        let mut table = create_table();
+        assert_eq!(table.load_from_str(input).unwrap(), 4);
+        assert_eq!(
+            table.lookup("10.0.1.200").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 3))
+        );
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            table.lookup("8.8.8.8").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
+        );
+
+        let mut restored = create_table();
+        assert_eq!(restored.load_from_str(&table.to_string()).unwrap(), 4);
+        assert_eq!(restored.to_string(), table.to_string());
+    }
+
+    #[test]
+    fn test_load_from_str_errors() {
+        let mut table = create_table();
+        let err = table
+            .load_from_str("10.0.0.0/8 192.168.0.1\n\n10.0.1.0/24\n")
+            .unwrap_err();
+        assert_eq!(
+            err,
+            LoadError {
+                line: 3,
+                error: RotabError::InvalidRoute("10.0.1.0/24".to_owned()),
+            }
+        );
+        assert_eq!(err.to_string(), "line 3: invalid route: 10.0.1.0/24");
+        assert_eq!(table.len(), 1);
+
+        let err = table
+            .load_from_str("# header\n10.0.0.0/33 192.168.0.1")
+            .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.error, RotabError::InvalidPrefixLen(33));
+
+        let err = table.load_from_str("10.0.0.0/8 gateway").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert!(matches!(err.error, RotabError::InvalidRoute(_)));
+    }
+
+    #[test]
+    fn test_bits_to_ipv4() {
+        let cases = [
+            ("192.168.1.77", 32, "192.168.1.77"),
+            ("192.168.1.77", 24, "192.168.1.0"),
+            ("192.168.1.77", 23, "192.168.0.0"),
+            ("10.255.0.1", 9, "10.128.0.0"),
+            ("255.255.255.255", 1, "128.0.0.0"),
+            ("255.255.255.255", 0, "0.0.0.0"),
+        ];
+
+        for (ip, n, network) in cases {
+            let bits = bits::<Ipv4Addr>(Ipv4Addr::from_str(ip).unwrap().to_bits(), 32);
+            assert_eq!(
+                bits_to_ipv4(&bits[..n]),
+                Ipv4Addr::from_str(network).unwrap()
+            );
         }
+
+        assert_eq!(bits_to_ipv4(&[1; 40]), Ipv4Addr::BROADCAST);
+        assert_eq!(
+            addr::bits_to_addr::<Ipv6Addr>(&[0, 0, 1]),
+            Ipv6Addr::from_str("2000::").unwrap()
+        );
     }
 
     #[test]
-    fn test_default_route() {
+    fn test_range_from_zero_is_not_default_route() {
         let mut table = create_table();
         table
-            .insert_range(
-                "0.0.0.0".to_owned(),
-                "255.255.255.255".to_owned(),
-                "0.0.0.0".to_owned(),
-            )
+            .insert_range("0.0.0.0", "200.0.0.0", Ipv4Addr::new(192, 168, 0, 1))
             .unwrap();
+
+        assert!(!table.contains_exact("0.0.0.0", "255.255.255.255").unwrap());
         assert_eq!(
-            table
-                .lookup("120.0.1.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "0.0.0.0"
+            table.lookup("10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
         );
         assert_eq!(
-            table
-                .lookup("10.0.0.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "0.0.0.0"
+            table.lookup("200.0.0.0").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
         );
+        assert_eq!(table.lookup("200.0.0.1").unwrap(), None);
+        assert_eq!(table.lookup("255.255.255.255").unwrap(), None);
+
+        // 0.0.0.0/1, 128.0.0.0/2, 192.0.0.0/5 and 200.0.0.0/32.
+        assert_eq!(table.len(), 4);
     }
 
     #[test]
-    fn test_specific_prefixes() {
+    fn test_routes_within() {
         let mut table = create_table();
-        table
-            .insert_range(
-                "10.0.1.0".to_owned(),
-                "10.0.1.255".to_owned(),
-                "192.168.0.1".to_owned(),
-            )
-            .unwrap();
-        table
-            .insert_range(
-                "10.0.2.0".to_owned(),
-                "10.0.2.255".to_owned(),
-                "192.168.0.2".to_owned(),
-            )
-            .unwrap();
-        table
-            .insert_range(
-                "10.0.3.0".to_owned(),
-                "10.0.3.255".to_owned(),
-                "192.168.0.3".to_owned(),
-            )
-            .unwrap();
+        let cidrs = [
+            ("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0)),
+            ("9.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 2)),
+            ("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 3)),
+            ("10.200.0.1/32", Ipv4Addr::new(192, 168, 0, 4)),
+            ("11.0.0.0/16", Ipv4Addr::new(192, 168, 0, 5)),
+            ("172.16.0.0/12", Ipv4Addr::new(192, 168, 0, 6)),
+        ];
+        for (cidr, dest) in cidrs {
+            table.insert_cidr(cidr, dest).unwrap();
+        }
+
+        let routes = table
+            .routes_within("10.0.0.0", "10.255.255.255")
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.network, m.prefix_len, m.dest))
+            .collect::<Vec<_>>();
         assert_eq!(
-            table
-                .lookup("10.0.1.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.0.1"
+            routes,
+            vec![
+                (Ipv4Addr::new(10, 0, 0, 0), 8, Ipv4Addr::new(192, 168, 0, 2)),
+                (
+                    Ipv4Addr::new(10, 0, 1, 0),
+                    24,
+                    Ipv4Addr::new(192, 168, 0, 3)
+                ),
+                (
+                    Ipv4Addr::new(10, 200, 0, 1),
+                    32,
+                    Ipv4Addr::new(192, 168, 0, 4)
+                ),
+            ]
         );
+
+        // Ranges spanning several blocks collect from each.
+        let prefixes = table
+            .routes_within("10.128.0.0", "11.255.255.255")
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.network, m.prefix_len))
+            .collect::<Vec<_>>();
         assert_eq!(
+            prefixes,
+            vec![
+                (Ipv4Addr::new(10, 200, 0, 1), 32),
+                (Ipv4Addr::new(11, 0, 0, 0), 16),
+            ]
+        );
+
+        assert!(
             table
-                .lookup("10.0.2.1".to_owned())
-                .unwrap()
+                .routes_within("12.0.0.0", "12.255.255.255")
                 .unwrap()
-                .to_string(),
-            "192.168.0.2"
+                .is_empty()
         );
         assert_eq!(
             table
-                .lookup("10.0.3.1".to_owned())
-                .unwrap()
+                .routes_within("0.0.0.0", "255.255.255.255")
                 .unwrap()
-                .to_string(),
-            "192.168.0.3"
+                .len(),
+            7
         );
+        assert!(matches!(
+            table.routes_within("10.0.0.1", "10.0.0.0"),
+            Err(RotabError::InvertedRange { .. })
+        ));
     }
 
     #[test]
-    fn test_overlapping_prefixes() {
+    fn test_covers() {
         let mut table = create_table();
+        assert!(!table.covers("10.0.1.0", "10.0.1.255").unwrap());
+
         table
-            .insert_range(
-                "0.0.0.0".to_owned(),
-                "127.255.255.255".to_owned(),
-                "1.1.1.1".to_owned(),
-            )
+            .insert_cidr("10.0.0.0/16", Ipv4Addr::new(192, 168, 0, 1))
             .unwrap();
         table
-            .insert_range(
-                "128.0.0.0".to_owned(),
-                "255.255.255.255".to_owned(),
-                "2.2.2.2".to_owned(),
-            )
+            .insert_cidr("172.16.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        // Covered by the /16, though not installed exactly.
+        assert!(table.covers("10.0.1.0", "10.0.1.255").unwrap());
+        assert!(!table.contains_exact("10.0.1.0", "10.0.1.255").unwrap());
+        assert!(table.covers("10.0.0.0", "10.0.255.255").unwrap());
+        assert!(!table.covers("10.0.0.0", "10.1.255.255").unwrap());
+
+        // Exact.
+        assert!(table.covers("172.16.1.0", "172.16.1.255").unwrap());
+        assert!(table.covers("172.16.1.7", "172.16.1.9").unwrap());
+        assert!(!table.covers("172.16.0.0", "172.16.1.255").unwrap());
+        assert!(!table.covers("172.16.1.0", "172.16.2.0").unwrap());
+
+        // Not covered at all.
+        assert!(!table.covers("192.168.0.0", "192.168.0.255").unwrap());
+    }
+
+    #[test]
+    fn test_lookup_excluding_longer_than() {
+        let mut table = create_table();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 8))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 24))
             .unwrap();
+
         assert_eq!(
-            table
-                .lookup("10.0.0.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "1.1.1.1"
+            table.lookup_excluding_longer_than("10.0.1.5", 16).unwrap(),
+            Some(Match {
+                dest: Ipv4Addr::new(192, 168, 0, 8),
+                prefix_len: 8,
+                network: Ipv4Addr::new(10, 0, 0, 0),
+            })
         );
         assert_eq!(
             table
-                .lookup("192.168.1.1".to_owned())
-                .unwrap()
+                .lookup_excluding_longer_than("10.0.1.5", 24)
                 .unwrap()
-                .to_string(),
-            "2.2.2.2"
+                .map(|m| m.prefix_len),
+            Some(24)
+        );
+        assert_eq!(
+            table.lookup_excluding_longer_than("10.0.1.5", 7).unwrap(),
+            None
+        );
+        assert_eq!(
+            table.lookup_excluding_longer_than("11.0.0.1", 32).unwrap(),
+            None
         );
     }
 
     #[test]
-    fn test_nested_prefixes() {
+    fn test_lookup_bounded() {
         let mut table = create_table();
         table
-            .insert_range(
-                "10.0.0.0".to_owned(),
-                "10.1.255.255".to_owned(),
-                "192.168.0.0".to_owned(),
-            )
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 24))
             .unwrap();
         table
-            .insert_range(
-                "10.0.1.0".to_owned(),
-                "10.0.1.255".to_owned(),
-                "192.168.0.1".to_owned(),
-            )
+            .insert_cidr("10.0.1.1/32", Ipv4Addr::new(192, 168, 0, 32))
             .unwrap();
+
         assert_eq!(
-            table
-                .lookup("10.0.0.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.0.0"
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 32))
         );
         assert_eq!(
-            table
-                .lookup("10.0.1.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.0.1"
+            table.lookup_bounded("10.0.1.1", 24).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 24))
         );
-    }
+        assert_eq!(
+            table.lookup_bounded("10.0.1.1", 32).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 32))
+        );
+        assert_eq!(table.lookup_bounded("10.0.1.1", 23).unwrap(), None);
+        assert_eq!(table.lookup_bounded("10.0.2.1", 32).unwrap(), None);
 
-    #[test]
-    fn test_single_ip() {
-        let mut table = create_table();
         table
-            .insert_range(
-                "192.168.1.1".to_owned(),
-                "192.168.1.1".to_owned(),
-                "192.168.1.1".to_owned(),
-            )
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
             .unwrap();
         assert_eq!(
-            table
-                .lookup("192.168.1.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.1.1"
+            table.lookup_bounded("10.0.1.1", 0).unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 0))
         );
-        assert_eq!(table.lookup("192.168.1.2".to_owned()).unwrap(), None);
     }
 
     #[test]
-    fn test_no_matching_prefix() {
-        let table = create_table();
-        assert_eq!(table.lookup("192.168.1.1".to_owned()).unwrap(), None);
+    fn test_eq() {
+        let cidrs = [
+            ("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0)),
+            ("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1)),
+            ("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2)),
+            ("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 3)),
+            ("192.168.1.1/32", Ipv4Addr::new(192, 168, 0, 4)),
+        ];
+
+        let mut a = create_table();
+        for (cidr, dest) in cidrs {
+            a.insert_cidr(cidr, dest).unwrap();
+        }
+
+        let mut b = create_table();
+        b.insert_cidr("172.16.0.0/12", Ipv4Addr::new(192, 168, 0, 5))
+            .unwrap();
+        for (cidr, dest) in cidrs.iter().rev() {
+            b.insert_cidr(cidr, *dest).unwrap();
+        }
+        assert_ne!(a, b);
+
+        b.remove_cidr("172.16.0.0/12").unwrap();
+        assert_eq!(a, b);
+
+        b.insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 9))
+            .unwrap();
+        assert_ne!(a, b);
+
+        b.insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        b.remove_cidr("0.0.0.0/0").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(create_table(), RouteTable::default());
     }
 
     #[test]
-    fn test_insertion_order() {
-        let mut table1 = create_table();
-        table1
-            .insert_range(
-                "10.0.0.0".to_owned(),
-                "10.1.255.255".to_owned(),
-                "192.168.0.0".to_owned(),
-            )
+    fn test_coverage_diff() {
+        let mut a = create_table();
+        a.insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
             .unwrap();
-        table1
-            .insert_range(
-                "10.0.1.0".to_owned(),
-                "10.0.1.255".to_owned(),
-                "192.168.0.1".to_owned(),
-            )
+        a.insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        a.insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
             .unwrap();
 
-        let mut table2 = create_table();
-        table2
-            .insert_range(
-                "10.0.1.0".to_owned(),
-                "10.0.1.255".to_owned(),
-                "192.168.0.1".to_owned(),
-            )
+        let mut b = a.clone();
+        b.insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 3))
             .unwrap();
-        table2
-            .insert_range(
-                "10.0.0.0".to_owned(),
-                "10.1.255.255".to_owned(),
-                "192.168.0.0".to_owned(),
-            )
+        // Same effect as the /8 it's under, so not a difference.
+        b.insert_cidr("10.5.0.0/16", Ipv4Addr::new(192, 168, 0, 1))
             .unwrap();
 
         assert_eq!(
-            table1
-                .lookup("10.0.1.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.0.1"
+            a.coverage_diff(&b),
+            vec![(
+                (Ipv4Addr::new(10, 0, 1, 0), 24),
+                CoverageState::Differs {
+                    this: Ipv4Addr::new(192, 168, 0, 2),
+                    other: Ipv4Addr::new(192, 168, 0, 3),
+                }
+            )]
         );
+        assert!(a.coverage_diff(&a).is_empty());
+
+        // Without a default route, the /8 only exists in one table, except
+        // where the other table has its own routes.
+        let mut c = create_table();
+        c.insert_cidr("10.0.0.0/9", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        a.remove_cidr("0.0.0.0/0").unwrap();
         assert_eq!(
-            table2
-                .lookup("10.0.1.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.0.1"
+            c.coverage_diff(&a),
+            vec![
+                (
+                    (Ipv4Addr::new(10, 0, 1, 0), 24),
+                    CoverageState::Differs {
+                        this: Ipv4Addr::new(192, 168, 0, 1),
+                        other: Ipv4Addr::new(192, 168, 0, 2),
+                    }
+                ),
+                (
+                    (Ipv4Addr::new(10, 128, 0, 0), 9),
+                    CoverageState::OnlyOther(Ipv4Addr::new(192, 168, 0, 1))
+                ),
+            ]
         );
         assert_eq!(
-            table1
-                .lookup("10.0.0.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.0.0"
+            a.coverage_diff(&c)[1],
+            (
+                (Ipv4Addr::new(10, 128, 0, 0), 9),
+                CoverageState::OnlyThis(Ipv4Addr::new(192, 168, 0, 1))
+            )
         );
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut live = Table::<u32>::new();
+        live.insert_cidr("10.0.0.0/24", 1).unwrap();
+        live.insert_cidr("10.0.1.0/24", 2).unwrap();
+        live.insert_cidr("10.0.2.0/24", 3).unwrap();
+
+        let mut desired = Table::<u32>::new();
+        desired.insert_cidr("10.0.0.0/24", 1).unwrap();
+        desired.insert_cidr("10.0.1.0/24", 20).unwrap();
+        desired.insert_cidr("10.0.0.0/8", 4).unwrap();
+
         assert_eq!(
-            table2
-                .lookup("10.0.0.1".to_owned())
-                .unwrap()
-                .unwrap()
-                .to_string(),
-            "192.168.0.0"
+            live.diff(&desired),
+            TableDiff {
+                added: vec![(Ipv4Addr::new(10, 0, 0, 0), 8)],
+                removed: vec![(Ipv4Addr::new(10, 0, 2, 0), 24)],
+                changed: vec![(Ipv4Addr::new(10, 0, 1, 0), 24)],
+            }
+        );
+
+        let diff = desired.diff(&live);
+        assert_eq!(diff.added, vec![(Ipv4Addr::new(10, 0, 2, 0), 24)]);
+        assert_eq!(diff.removed, vec![(Ipv4Addr::new(10, 0, 0, 0), 8)]);
+
+        let diff = live.diff(&live.clone());
+        assert!(diff.added.is_empty() && diff.removed.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn test_lookup_u32() {
+        let mut table = create_table();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        assert_eq!(
+            table.lookup_u32(0x0A00_0101),
+            Some(&Ipv4Addr::new(192, 168, 0, 2))
+        );
+        for (ip, s) in [
+            (0x0A00_0101, "10.0.1.1"),
+            (0x0A00_0201, "10.0.2.1"),
+            (0x0808_0808, "8.8.8.8"),
+            (u32::from_be_bytes([10, 0, 1, 255]), "10.0.1.255"),
+        ] {
+            assert_eq!(table.lookup_u32(ip).copied(), table.lookup(s).unwrap());
+        }
+
+        assert_eq!(RouteTable::new().lookup_u32(0x0A00_0101), None);
+    }
+
+    #[test]
+    fn test_insert_masked() {
+        let mut table = create_table();
+        let masks = [
+            ("10.0.1.5", "255.255.255.0", "10.0.1.0/24"),
+            ("10.0.2.5", "255.255.255.255", "10.0.2.5/32"),
+            ("172.16.0.0", "255.240.0.0", "172.16.0.0/12"),
+            ("8.8.8.8", "0.0.0.0", "0.0.0.0/0"),
+        ];
+        for (i, (addr, netmask, _)) in masks.into_iter().enumerate() {
+            table
+                .insert_masked(
+                    addr.parse().unwrap(),
+                    netmask.parse().unwrap(),
+                    Ipv4Addr::new(192, 168, 0, i as u8),
+                )
+                .unwrap();
+        }
+
+        assert_eq!(table.len(), masks.len());
+        for (i, (_, _, cidr)) in masks.into_iter().enumerate() {
+            let (network, prefix_len) = cidr.split_once('/').unwrap();
+            let network = network.parse::<Ipv4Addr>().unwrap();
+            let end = network.to_bits() | !mask::<u32>(prefix_len.parse().unwrap());
+            assert!(
+                table
+                    .contains_exact(network, Ipv4Addr::from_bits(end))
+                    .unwrap()
+            );
+            assert_eq!(
+                table.lookup(network).unwrap(),
+                Some(Ipv4Addr::new(192, 168, 0, i as u8))
+            );
+        }
+
+        for netmask in ["255.0.255.0", "0.255.255.255", "255.255.255.253"] {
+            let netmask = netmask.parse().unwrap();
+            assert_eq!(
+                table.insert_masked(
+                    Ipv4Addr::new(10, 0, 0, 0),
+                    netmask,
+                    Ipv4Addr::new(192, 168, 1, 1)
+                ),
+                Err(RotabError::InvalidNetmask(netmask.into()))
+            );
+        }
+        assert_eq!(table.len(), masks.len());
+    }
+
+    #[test]
+    fn test_insert_u32_prefix() {
+        let prefixes = [
+            (0x0000_0000, 0, "0.0.0.0/0"),
+            (0x0A00_0000, 8, "10.0.0.0/8"),
+            (0x0A00_0105, 24, "10.0.1.5/24"),
+            (0x0A00_0180, 25, "10.0.1.128/25"),
+            (0xC0A8_0001, 32, "192.168.0.1/32"),
+        ];
+
+        let mut from_u32 = Table::<usize>::new();
+        let mut from_cidr = Table::<usize>::new();
+        for (i, (addr, prefix_len, cidr)) in prefixes.into_iter().enumerate() {
+            assert_eq!(
+                from_u32.insert_u32_prefix(addr, prefix_len, i).unwrap(),
+                None
+            );
+            from_cidr.insert_cidr(cidr, i).unwrap();
+        }
+
+        assert!(from_u32.routes().eq(from_cidr.routes()));
+        for ip in [
+            "10.0.1.1",
+            "10.0.1.200",
+            "10.2.0.1",
+            "192.168.0.1",
+            "192.168.0.2",
+        ] {
+            assert_eq!(from_u32.lookup(ip).unwrap(), from_cidr.lookup(ip).unwrap());
+        }
+
+        assert_eq!(
+            from_u32.insert_u32_prefix(0x0A00_0000, 8, 9).unwrap(),
+            Some(1)
+        );
+        assert_eq!(
+            from_u32.insert_u32_prefix(0x0A00_0000, 33, 9),
+            Err(RotabError::InvalidPrefixLen(33))
         );
     }
 }
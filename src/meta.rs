@@ -0,0 +1,21 @@
+/// The protocol or configuration a route was learned from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RouteSource {
+    #[default]
+    Static,
+    Bgp,
+    Ospf,
+}
+
+/// Metadata stored with a route, next to its destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct RouteMeta {
+    pub source: RouteSource,
+    pub admin_distance: u8,
+    /// When the route was installed, in a unit chosen by the caller (e.g.
+    /// seconds since the Unix epoch).
+    pub timestamp: u64,
+    /// Marks the route as preferred by policy: lookups return the longest
+    /// matching preferred route over any more specific route that isn't.
+    pub preferred: bool,
+}
@@ -0,0 +1,111 @@
+use alloc::{format, vec::Vec};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+
+use crate::{Address, Table, addr::mask};
+
+// Tables are stored as a flat list of routes rather than the node graph, so
+// the format doesn't depend on the trie's internal layout.
+#[derive(Serialize, Deserialize)]
+struct RouteEntry<A, D> {
+    prefix: A,
+    prefix_len: u8,
+    dest: D,
+}
+
+impl<V: Serialize, A: Address + Serialize> Serialize for Table<V, A> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.routes().map(|(prefix, prefix_len, dest)| RouteEntry {
+            prefix,
+            prefix_len,
+            dest,
+        }))
+    }
+}
+
+impl<'de, V: Deserialize<'de>, A: Address + Deserialize<'de>> Deserialize<'de> for Table<V, A> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let routes = Vec::<RouteEntry<A, V>>::deserialize(deserializer)?;
+
+        let mut table = Table::new();
+        for route in routes {
+            if route.prefix_len as u32 > A::BITS {
+                return Err(D::Error::custom(format!(
+                    "invalid prefix length {}",
+                    route.prefix_len
+                )));
+            }
+
+            let network = route.prefix.to_bits() & mask(route.prefix_len);
+            table.insert_prefix(network, route.prefix_len, route.dest);
+        }
+
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::{RouteTable, Table6};
+
+    #[test]
+    fn test_round_trip() {
+        let mut table = RouteTable::new();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.128/25", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(
+            json,
+            concat!(
+                r#"[{"prefix":"0.0.0.0","prefix_len":0,"dest":"192.168.0.0"},"#,
+                r#"{"prefix":"10.0.0.0","prefix_len":8,"dest":"192.168.0.1"},"#,
+                r#"{"prefix":"10.0.1.0","prefix_len":24,"dest":"192.168.0.2"},"#,
+                r#"{"prefix":"10.0.1.128","prefix_len":25,"dest":"192.168.0.3"}]"#,
+            )
+        );
+
+        let restored: RouteTable = serde_json::from_str(&json).unwrap();
+        for ip in ["8.8.8.8", "10.2.0.1", "10.0.1.1", "10.0.1.200", "10.0.2.1"] {
+            assert_eq!(restored.lookup(ip).unwrap(), table.lookup(ip).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_invalid_prefix_len() {
+        let json = r#"[{"prefix":"10.0.0.0","prefix_len":33,"dest":"192.168.0.1"}]"#;
+        assert!(serde_json::from_str::<RouteTable>(json).is_err());
+    }
+
+    #[test]
+    fn test_round_trip_v6() {
+        let mut table = Table6::new();
+        table.insert_cidr("::/0", 0).unwrap();
+        table.insert_cidr("2001:db8::/32", 1).unwrap();
+
+        let json = serde_json::to_string(&table).unwrap();
+        assert_eq!(
+            json,
+            r#"[{"prefix":"::","prefix_len":0,"dest":0},{"prefix":"2001:db8::","prefix_len":32,"dest":1}]"#
+        );
+
+        let restored: Table6<u32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.lookup("2001:db8::1").unwrap(), Some(1));
+        assert_eq!(restored.lookup("2001:db9::1").unwrap(), Some(0));
+
+        let json = r#"[{"prefix":"2001:db8::","prefix_len":129,"dest":1}]"#;
+        assert!(serde_json::from_str::<Table6<u32>>(json).is_err());
+    }
+}
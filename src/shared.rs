@@ -0,0 +1,143 @@
+use std::{
+    net::Ipv4Addr,
+    sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+use crate::{Address, IntoAddr, Match, RotabError, Table};
+
+/// A `Table` that can be shared between threads. Clones are cheap and refer
+/// to the same table: lookups take a read lock, so they run concurrently,
+/// while updates take the write lock.
+pub struct SharedTable<V, A: Address = Ipv4Addr> {
+    inner: Arc<RwLock<Table<V, A>>>,
+}
+
+impl<V, A: Address> Clone for SharedTable<V, A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<V, A: Address> Default for SharedTable<V, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, A: Address> From<Table<V, A>> for SharedTable<V, A> {
+    fn from(table: Table<V, A>) -> Self {
+        Self {
+            inner: Arc::new(RwLock::new(table)),
+        }
+    }
+}
+
+impl<V, A: Address> SharedTable<V, A> {
+    pub fn new() -> Self {
+        Table::new().into()
+    }
+
+    /// Locks the table for reading. A panic in another thread while it held
+    /// the lock doesn't leave the trie inconsistent, so poisoning is ignored.
+    pub fn read(&self) -> RwLockReadGuard<'_, Table<V, A>> {
+        self.inner.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Locks the table for writing, blocking until every reader is done.
+    pub fn write(&self) -> RwLockWriteGuard<'_, Table<V, A>> {
+        self.inner.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn insert_range(
+        &self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        self.write().insert_range(start, end, dest)
+    }
+
+    pub fn insert_cidr(&self, cidr: &str, dest: V) -> Result<Option<V>, RotabError> {
+        self.write().insert_cidr(cidr, dest)
+    }
+
+    pub fn remove_range(
+        &self,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<bool, RotabError> {
+        self.write().remove_range(start, end)
+    }
+
+    pub fn lookup(&self, ip: impl IntoAddr<A>) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        self.read().lookup(ip)
+    }
+
+    pub fn lookup_detailed(&self, ip: impl IntoAddr<A>) -> Result<Option<Match<V, A>>, RotabError>
+    where
+        V: Clone,
+    {
+        self.read().lookup_detailed(ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, thread};
+
+    use super::*;
+
+    #[test]
+    fn test_concurrent_lookups() {
+        let table = SharedTable::new();
+        table
+            .insert_range("0.0.0.0", "255.255.255.255", Ipv4Addr::new(192, 168, 0, 0))
+            .unwrap();
+        for i in 0..256_u32 {
+            table
+                .insert_cidr(&format!("10.{i}.0.0/16"), Ipv4Addr::from_bits(i))
+                .unwrap();
+        }
+
+        let workers = (0..8_u32)
+            .map(|w| {
+                let table = table.clone();
+                thread::spawn(move || {
+                    for i in 0..256_u32 {
+                        let ip = Ipv4Addr::new(10, i as u8, w as u8, 1);
+                        assert_eq!(table.lookup(ip).unwrap(), Some(Ipv4Addr::from_bits(i)));
+                    }
+                    assert_eq!(
+                        table.lookup("8.8.8.8").unwrap(),
+                        Some(Ipv4Addr::new(192, 168, 0, 0))
+                    );
+                })
+            })
+            .collect::<Vec<_>>();
+
+        for worker in workers {
+            worker.join().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_writer_visible_to_readers() {
+        let table = SharedTable::<u32>::new();
+        let reader = table.clone();
+
+        thread::spawn(move || table.insert_cidr("10.0.0.0/8", 1).unwrap())
+            .join()
+            .unwrap();
+
+        assert_eq!(reader.lookup("10.1.2.3").unwrap(), Some(1));
+        assert_eq!(reader.read().len(), 1);
+    }
+}
@@ -0,0 +1,118 @@
+use alloc::sync::Arc;
+use core::net::Ipv4Addr;
+
+use crate::{Address, IntoAddr, RotabError, Table};
+
+/// A frozen copy of a `Table`, taken with `Table::snapshot`. Readers can
+/// keep looking up routes in it while the writer goes on updating the
+/// table, and the writer can later roll back to it with `Table::restore`.
+pub struct TableSnapshot<V, A: Address = Ipv4Addr> {
+    table: Table<V, A>,
+}
+
+impl<V, A: Address> TableSnapshot<V, A> {
+    pub fn lookup(&self, ip: impl IntoAddr<A>) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        self.table.lookup(ip)
+    }
+
+    /// Returns the number of distinct prefixes in the snapshot.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+impl<V: Clone, A: Address> Table<V, A> {
+    /// Copies the table into an immutable snapshot, which can be handed to
+    /// readers while this table keeps changing. The copy is a full clone,
+    /// taking time and memory in proportion to the table's size, so take
+    /// snapshots per batch of updates rather than per update.
+    pub fn snapshot(&self) -> Arc<TableSnapshot<V, A>> {
+        Arc::new(TableSnapshot {
+            table: self.clone(),
+        })
+    }
+
+    /// Replaces every route with the ones in `snapshot`. The generation
+    /// still moves forward, rather than back to the snapshot's.
+    pub fn restore(&mut self, snapshot: &TableSnapshot<V, A>) {
+        let generation = self.generation;
+        self.clone_from(&snapshot.table);
+        self.generation = generation + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, sync::Arc, thread};
+
+    use crate::RouteTable;
+
+    #[test]
+    fn test_snapshot() {
+        let mut table = RouteTable::new();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 2))
+            .unwrap();
+
+        let snapshot = table.snapshot();
+        table
+            .insert_cidr("10.0.1.0/24", Ipv4Addr::new(192, 168, 0, 3))
+            .unwrap();
+        table.remove_cidr("10.0.0.0/8").unwrap();
+        table
+            .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 4))
+            .unwrap();
+
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(
+            snapshot.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(
+            snapshot.lookup("10.9.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 1))
+        );
+        assert_eq!(snapshot.lookup("8.8.8.8").unwrap(), None);
+
+        assert_eq!(
+            table.lookup("10.9.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 4))
+        );
+
+        table.restore(&snapshot);
+        assert_eq!(table.len(), 2);
+        assert_eq!(
+            table.lookup("10.0.1.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 0, 2))
+        );
+        assert_eq!(table.lookup("8.8.8.8").unwrap(), None);
+    }
+
+    #[test]
+    fn test_snapshot_across_threads() {
+        let mut table = RouteTable::new();
+        table
+            .insert_cidr("10.0.0.0/8", Ipv4Addr::new(192, 168, 0, 1))
+            .unwrap();
+
+        let snapshot = table.snapshot();
+        let reader = {
+            let snapshot = Arc::clone(&snapshot);
+            thread::spawn(move || snapshot.lookup("10.0.0.1").unwrap())
+        };
+        table.clear();
+
+        assert_eq!(reader.join().unwrap(), Some(Ipv4Addr::new(192, 168, 0, 1)));
+        assert!(table.is_empty());
+    }
+}
@@ -0,0 +1,147 @@
+use alloc::collections::BTreeMap;
+use core::net::Ipv4Addr;
+
+use crate::{Address, IntoAddr, RotabError, Table};
+
+/// A set of isolated tables keyed by a VRF (virtual routing and forwarding)
+/// id, for multi-tenant routing. Each VRF's table is created on its first
+/// insert, and a lookup only sees the routes of its own VRF.
+pub struct VrfTable<V, A: Address = Ipv4Addr> {
+    tables: BTreeMap<u32, Table<V, A>>,
+}
+
+impl<V, A: Address> Default for VrfTable<V, A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V, A: Address> VrfTable<V, A> {
+    pub fn new() -> Self {
+        Self {
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the table of `vrf`, if anything was inserted into it.
+    pub fn table(&self, vrf: u32) -> Option<&Table<V, A>> {
+        self.tables.get(&vrf)
+    }
+
+    /// Returns the table of `vrf`, creating it empty if needed.
+    pub fn table_mut(&mut self, vrf: u32) -> &mut Table<V, A> {
+        self.tables.entry(vrf).or_default()
+    }
+
+    /// Iterates over the VRF ids that have a table, in ascending order.
+    pub fn vrfs(&self) -> impl Iterator<Item = u32> + '_ {
+        self.tables.keys().copied()
+    }
+
+    /// Like `Table::insert_range`, in the table of `vrf`.
+    pub fn insert_range(
+        &mut self,
+        vrf: u32,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+        dest: V,
+    ) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        // Validate first, so a failed insert doesn't create the VRF.
+        let (start, end) = Table::<V, A>::parse_range(start, end)?;
+
+        self.table_mut(vrf)
+            .insert_range(A::from_bits(start), A::from_bits(end), dest)
+    }
+
+    /// Like `Table::remove_range`, in the table of `vrf`.
+    pub fn remove_range(
+        &mut self,
+        vrf: u32,
+        start: impl IntoAddr<A>,
+        end: impl IntoAddr<A>,
+    ) -> Result<bool, RotabError> {
+        let (start, end) = (start.into_addr()?, end.into_addr()?);
+        match self.tables.get_mut(&vrf) {
+            Some(table) => table.remove_range(start, end),
+            None => Ok(false),
+        }
+    }
+
+    /// Looks up `ip` in the table of `vrf` only. A VRF without a table has
+    /// no routes.
+    pub fn lookup(&self, vrf: u32, ip: impl IntoAddr<A>) -> Result<Option<V>, RotabError>
+    where
+        V: Clone,
+    {
+        let ip = ip.into_addr()?;
+        match self.tables.get(&vrf) {
+            Some(table) => table.lookup(ip),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::VrfTable;
+    use crate::RotabError;
+
+    #[test]
+    fn test_vrf_isolation() {
+        let mut table = VrfTable::<Ipv4Addr>::new();
+        table
+            .insert_range(1, "10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 1, 1))
+            .unwrap();
+        table
+            .insert_range(2, "10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 2, 1))
+            .unwrap();
+        table
+            .insert_range(
+                2,
+                "0.0.0.0",
+                "255.255.255.255",
+                Ipv4Addr::new(192, 168, 2, 0),
+            )
+            .unwrap();
+
+        assert_eq!(
+            table.lookup(1, "10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 1, 1))
+        );
+        assert_eq!(
+            table.lookup(2, "10.0.0.1").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 2, 1))
+        );
+        // VRF 2's default route doesn't leak into VRF 1.
+        assert_eq!(table.lookup(1, "8.8.8.8").unwrap(), None);
+        assert_eq!(
+            table.lookup(2, "8.8.8.8").unwrap(),
+            Some(Ipv4Addr::new(192, 168, 2, 0))
+        );
+        assert_eq!(table.lookup(3, "10.0.0.1").unwrap(), None);
+        assert_eq!(table.vrfs().collect::<Vec<_>>(), vec![1, 2]);
+
+        assert!(table.remove_range(1, "10.0.0.0", "10.0.0.255").unwrap());
+        assert!(!table.remove_range(3, "10.0.0.0", "10.0.0.255").unwrap());
+        assert_eq!(table.lookup(1, "10.0.0.1").unwrap(), None);
+        assert_eq!(table.table(2).unwrap().len(), 2);
+        assert!(table.table(3).is_none());
+    }
+
+    #[test]
+    fn test_vrf_invalid_input() {
+        let mut table = VrfTable::<Ipv4Addr>::new();
+        assert!(matches!(
+            table.insert_range(1, "10.0.0.255", "10.0.0.0", Ipv4Addr::new(192, 168, 1, 1)),
+            Err(RotabError::InvertedRange { .. })
+        ));
+        assert!(table.lookup(1, "10.0.0").is_err());
+        // A failed insert doesn't create the VRF.
+        assert_eq!(table.vrfs().count(), 0);
+    }
+}
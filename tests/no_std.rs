@@ -0,0 +1,39 @@
+//! Uses the crate from a `no_std` crate. Run with `--no-default-features`
+//! to check that the library itself builds without `std`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::{borrow::ToOwned, vec};
+use core::net::Ipv4Addr;
+
+use rotab::{RouteTable, Table6};
+
+#[test]
+fn test_no_std_lookup() {
+    let mut table = RouteTable::new();
+    table
+        .insert_cidr("0.0.0.0/0", Ipv4Addr::new(192, 168, 0, 0))
+        .unwrap();
+    table
+        .insert_range("10.0.0.0", "10.0.0.255", Ipv4Addr::new(192, 168, 0, 1))
+        .unwrap();
+
+    assert_eq!(
+        table.lookup("10.0.0.1").unwrap(),
+        Some(Ipv4Addr::new(192, 168, 0, 1))
+    );
+    assert_eq!(
+        table.lookup(Ipv4Addr::new(8, 8, 8, 8)).unwrap(),
+        Some(Ipv4Addr::new(192, 168, 0, 0))
+    );
+    assert_eq!(
+        table.match_cidr("10.0.0.1").unwrap().map(|(cidr, _)| cidr),
+        Some("10.0.0.0/24".to_owned())
+    );
+
+    let mut table = Table6::new();
+    table.insert_cidr("2001:db8::/32", vec![1, 2]).unwrap();
+    assert_eq!(table.lookup_ecmp("2001:db8::1").unwrap(), Some(&[1, 2][..]));
+}